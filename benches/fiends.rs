@@ -0,0 +1,85 @@
+//! How long a fight tick takes with a room full of fiends.
+//!
+//! Run with `cargo bench`. Each line is the average time per tick with
+//! that many fiends scattered around a large room.
+//!
+//! As a baseline, the same benchmark on the last tree where each fiend
+//! ran its own A* search (before flow fields, and before difficulty
+//! settings, so with the waves of that time) against flow fields, on
+//! one machine:
+//!
+//! ```text
+//! fiends   A*           flow fields
+//!     50    90.78 ms     0.11 ms
+//!    100   171.33 ms     0.21 ms
+//!    200   345.12 ms     0.31 ms
+//!    400   724.04 ms     0.45 ms
+//! ```
+
+extern crate ludum_dare_37;
+
+use ludum_dare_37::{Game, Settings};
+use ludum_dare_37::fiends::make_wave;
+use ludum_dare_37::model::*;
+
+use std::time::Instant;
+
+const WIDTH: usize = 120;
+const HEIGHT: usize = 60;
+const TICKS: u32 = 20;
+
+fn main() {
+    for &count in &[50, 100, 200, 400] {
+        let mut game = crowded_game(count);
+        let start = Instant::now();
+        for _ in 0..TICKS {
+            game.tick();
+        }
+        let per_tick = start.elapsed() / TICKS;
+        println!("{:4} fiends: {:8.2} ms/tick", count, per_tick.as_secs_f64() * 1e3);
+    }
+}
+
+// A game in the middle of a fight with the given number of fiends, and
+// a Thing and player which won't be destroyed before the benchmark ends.
+fn crowded_game(count: usize) -> Game {
+    let mut settings = Settings::new(37);
+    settings.width = WIDTH;
+    settings.height = HEIGHT;
+    let mut game = Game::new(settings);
+    let world_data = &mut game.world_data;
+
+    let (goal_x, goal_y) = world_data.goal_location;
+    world_data.statics[goal_y][goal_x] = Some(Goal {
+        health: usize::MAX,
+        max_health: usize::MAX,
+    });
+    world_data.player_info.health = usize::MAX;
+    world_data.player_info.max_health = usize::MAX;
+
+    let mut fiends = Vec::new();
+    let mut wave = 1;
+    while fiends.len() < count {
+        fiends.extend(make_wave(wave, Difficulty::Normal, &mut world_data.rng));
+        wave += 1;
+    }
+
+    // Scatter them over every other row, away from the walls.
+    let mut spaces = (2..HEIGHT - 2)
+        .step_by(2)
+        .flat_map(|y| (2..WIDTH - 2).step_by(3).map(move |x| (x, y)))
+        .filter(|&(x, y)| world_data.statics[y][x].is_none() && world_data.mobiles[y][x].is_none())
+        .collect::<Vec<_>>()
+        .into_iter();
+    for &info in fiends.iter().take(count) {
+        let (x, y) = spaces.next().expect("Not enough space for the fiends");
+        world_data.mobiles[y][x] = Some(Fiend { info });
+        world_data.fiends.insert((x, y));
+    }
+
+    game.state = GameState::Fight {
+        to_spawn: Vec::new(),
+        player_moved: false,
+    };
+    game
+}
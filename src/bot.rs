@@ -0,0 +1,330 @@
+//! A computer player, for finding out how far the game can be got
+//! through without anyone at the keyboard.
+//!
+//! The bot plays through the same commands as a person would: it
+//! works the construct menus a key at a time, and in a fight it walks
+//! the player towards whatever most needs hitting or fixing.
+
+use game::{Game, Settings};
+use model::*;
+use util::{adjacency, distance};
+
+use std::collections::VecDeque;
+
+/// How many turrets the bot builds before it spends anything on
+/// upgrades.
+pub const MAX_TURRETS: usize = 8;
+
+// How far from the Thing the bot puts its turrets.
+const TURRET_RING: usize = 3;
+
+// How close to the Thing fiends have to get before the player goes
+// after them.
+const GUARD_DISTANCE: usize = 4;
+
+// How many menu commands the bot can give in one construct phase
+// before it's assumed to be lost, and the game is given up on.
+const MAX_MENU_COMMANDS: usize = 10_000;
+
+// How many ticks a wave can go on for before the game is given up on,
+// in case the fiends and the bot are stuck with neither able to win.
+const MAX_WAVE_TICKS: u64 = 10_000;
+
+/// How a game played by the bot turned out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// The wave the game ended on.
+    pub wave: usize,
+    /// Why the game ended, or None if the bot got through every wave
+    /// it was asked to.
+    pub game_over: Option<String>,
+    pub ticks: u64,
+}
+
+/// Play a game from start to finish, stopping early if the bot gets
+/// through 'max_wave' waves.
+pub fn play(settings: Settings, max_wave: usize) -> Outcome {
+    let mut game = Game::new(settings);
+    let mut menu_commands = 0;
+    let mut wave_ticks = 0;
+    loop {
+        match game.state {
+            GameOver { ref msg } => {
+                return Outcome {
+                    wave: game.world_data.wave,
+                    game_over: Some(msg.clone()),
+                    ticks: game.ticks,
+                }
+            }
+            Construct { .. } if game.world_data.wave >= max_wave => {
+                return Outcome {
+                    wave: game.world_data.wave,
+                    game_over: None,
+                    ticks: game.ticks,
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(command) = command(&game) {
+            game.command(command);
+        }
+        let stuck = if let Fight { .. } = game.state {
+            menu_commands = 0;
+            wave_ticks += 1;
+            game.tick();
+            Some("The wave never ended!").filter(|_| wave_ticks > MAX_WAVE_TICKS)
+        } else {
+            wave_ticks = 0;
+            menu_commands += 1;
+            Some("The bot got lost in the menus!").filter(|_| menu_commands > MAX_MENU_COMMANDS)
+        };
+        if let Some(msg) = stuck {
+            return Outcome {
+                wave: game.world_data.wave,
+                game_over: Some(msg.to_string()),
+                ticks: game.ticks,
+            };
+        }
+    }
+}
+
+/// What the bot does next, or None if there's nothing worth doing.
+pub fn command(game: &Game) -> Option<Command> {
+    let world_data = &game.world_data;
+    match game.state {
+        Startup => Some(Command::Select),
+        Construct { menu, menu_index } => Some(work_menu(world_data, menu, menu_index)),
+        Fight { .. } => fight(world_data),
+        GameOver { .. } | End => None,
+    }
+}
+
+// Something the bot wants to do in the construct phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Plan {
+    Build(Buildable, (usize, usize)),
+    Upgrade((usize, usize), TurretStat),
+    Train(PlayerStat),
+    Fight,
+}
+
+// What to spend the cash on next: a turret, then obstacles all round
+// the Thing, then more turrets, then the cheapest upgrade which makes
+// things hit harder or last longer. If the next thing can't be
+// afforded yet, the bot saves up for it.
+fn plan(world_data: &WorldData) -> Plan {
+    let turret = Buildable::Turret(TurretForm::Basic);
+    let turret_spot = ring(world_data, TURRET_RING).into_iter().find(|&xy| is_free(world_data, xy));
+    let obstacle_spot = ring(world_data, 1).into_iter().take(4).find(|&xy| is_free(world_data, xy));
+
+    let (wanted, spot) = match (turret_spot, obstacle_spot) {
+        (Some(xy), _) if world_data.turrets.is_empty() => (turret, xy),
+        (_, Some(xy)) => (Buildable::Obstacle, xy),
+        (Some(xy), _) if world_data.turrets.len() < MAX_TURRETS => (turret, xy),
+        _ => return upgrade(world_data),
+    };
+    if wanted.cost() <= world_data.cash {
+        Plan::Build(wanted, spot)
+    } else {
+        Plan::Fight
+    }
+}
+
+fn upgrade(world_data: &WorldData) -> Plan {
+    let mut options = Vec::new();
+    for &(x, y) in &world_data.turrets {
+        if let Some(Turret { info }) = world_data.statics[y][x] {
+            for &stat in &[TurretStat::Damage, TurretStat::Cooldown] {
+                if let Some(cost) = stat.cost(&info) {
+                    options.push((cost, Plan::Upgrade((x, y), stat)));
+                }
+            }
+        }
+    }
+    for &stat in &[PlayerStat::Health, PlayerStat::Damage] {
+        if let Some(cost) = stat.cost(&world_data.player_info) {
+            options.push((cost, Plan::Train(stat)));
+        }
+    }
+    match options.into_iter().min_by_key(|&(cost, _)| cost) {
+        Some((cost, plan)) if cost <= world_data.cash => plan,
+        _ => Plan::Fight,
+    }
+}
+
+// The spaces in the room on the edge of a square around the Thing,
+// starting with the middle of each side, then the corners.
+fn ring(world_data: &WorldData, r: usize) -> Vec<(usize, usize)> {
+    let (gx, gy) = world_data.goal_location;
+    let r = r as isize;
+    let offsets = [(0, -r), (r, 0), (0, r), (-r, 0), (-r, -r), (r, -r), (r, r), (-r, r)];
+    offsets.iter()
+        .map(|&(dx, dy)| (gx as isize + dx, gy as isize + dy))
+        .filter(|&(x, y)| {
+            x > 0 && y > 0 && x < world_data.width() as isize - 1 &&
+            y < world_data.height() as isize - 1
+        })
+        .map(|(x, y)| (x as usize, y as usize))
+        .collect()
+}
+
+// Whether something can be built in a space without getting in the
+// player's way.
+fn is_free(world_data: &WorldData, (x, y): (usize, usize)) -> bool {
+    world_data.statics[y][x].is_none() && world_data.mobiles[y][x].is_none()
+}
+
+// The next key to press to carry out the plan from the menu we're in.
+fn work_menu(world_data: &WorldData, menu: Menu, index: usize) -> Command {
+    let plan = plan(world_data);
+    let root_item = match plan {
+        Plan::Build(..) => RootItem::Build,
+        Plan::Upgrade(..) => RootItem::Upgrade,
+        Plan::Train(..) => RootItem::Train,
+        Plan::Fight => {
+            return match menu {
+                Menu::Place(..) => Command::Select,
+                _ => Command::Continue,
+            }
+        }
+    };
+
+    match (menu, plan) {
+        (Menu::Root, _) => towards(index, ROOT_ITEMS.iter().position(|&i| i == root_item).unwrap()),
+        (Menu::Build, Plan::Build(buildable, _)) => {
+            towards(index, BUILDABLES.iter().position(|&b| b == buildable).unwrap())
+        }
+        (Menu::Place(_, (x, y), _), Plan::Build(_, (to_x, to_y))) => {
+            if x < to_x {
+                Command::Move(Dir::E)
+            } else if x > to_x {
+                Command::Move(Dir::W)
+            } else if y < to_y {
+                Command::Move(Dir::S)
+            } else if y > to_y {
+                Command::Move(Dir::N)
+            } else {
+                Command::Select
+            }
+        }
+        // Put down whatever this is where it is, and get on with the
+        // plan.
+        (Menu::Place(..), _) => Command::Select,
+        (Menu::Upgrade, Plan::Upgrade(xy, _)) => {
+            towards(index, world_data.turrets.iter().position(|&t| t == xy).unwrap())
+        }
+        (Menu::UpgradeTurret(at), Plan::Upgrade(xy, stat)) if at == xy => {
+            towards(index, stat.index())
+        }
+        (Menu::Train, Plan::Train(stat)) => towards(index, stat.index()),
+        _ => Command::Back,
+    }
+}
+
+// Move the menu cursor towards an item, or pick it once it's there.
+fn towards(index: usize, wanted: usize) -> Command {
+    if index < wanted {
+        Command::Move(Dir::S)
+    } else if index > wanted {
+        Command::Move(Dir::N)
+    } else {
+        Command::Select
+    }
+}
+
+// Where the player goes in a fight. Fixing things comes first if
+// anything is badly damaged, or the player is; otherwise the player
+// hits back at whatever's attacking them, or goes after the fiend
+// closest to the Thing if it's getting close.
+fn fight(world_data: &WorldData) -> Option<Command> {
+    let player = world_data.player_info;
+    let goal_xy = world_data.goal_location;
+
+    let mut damaged = world_data.turrets
+        .iter()
+        .chain(world_data.obstacles.iter())
+        .chain(Some(&goal_xy))
+        .filter_map(|&(x, y)| {
+            let (health, max_health) = match world_data.statics[y][x] {
+                Some(Turret { info }) => (info.health, info.max_health),
+                Some(Obstacle { health, max_health }) |
+                Some(Goal { health, max_health }) => (health, max_health),
+                _ => return None,
+            };
+            if health < max_health {
+                Some(((x, y), health * 100 / max_health))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    damaged.sort_by_key(|&(xy, percent)| (percent, distance(xy, player.location)));
+    let most_damaged = damaged.first().cloned();
+    let nearest_fiend = world_data.fiends
+        .iter()
+        .cloned()
+        .filter(|&(x, y)| match world_data.mobiles[y][x] {
+            // Archers won't come any closer, so they have to be gone
+            // after wherever they are.
+            Some(Fiend { info }) => {
+                info.archetype == Archetype::Archer || distance((x, y), goal_xy) <= GUARD_DISTANCE
+            }
+            _ => false,
+        })
+        .min_by_key(|&xy| distance(xy, goal_xy));
+
+    // Anything attacking the player gets hit back first.
+    let attacker = adjacency(player.location, (world_data.width(), world_data.height()))
+        .into_iter()
+        .find(|xy| world_data.fiends.contains(xy));
+
+    let target = match (most_damaged, attacker.or(nearest_fiend)) {
+        (Some((xy, percent)), _) if percent < 50 || player.health * 3 < player.max_health => xy,
+        (_, Some(xy)) => xy,
+        (Some((xy, _)), None) => xy,
+        (None, None) => return None,
+    };
+    step_towards(world_data, target).map(Command::Move)
+}
+
+// The direction of the first step on the shortest way for the player
+// to get to (and then bump into) a space, if there's a way.
+fn step_towards(world_data: &WorldData, target: (usize, usize)) -> Option<Dir> {
+    let size = (world_data.width(), world_data.height());
+    let from = world_data.player_info.location;
+
+    // Work outwards from the target through everywhere the player can
+    // walk, until the player is reached.
+    let mut seen = Grid::new(size.0, size.1, false);
+    let mut queue = VecDeque::new();
+    seen[target.1][target.0] = true;
+    queue.push_back(target);
+    while let Some(xy) = queue.pop_front() {
+        for (x, y) in adjacency(xy, size) {
+            if seen[y][x] {
+                continue;
+            }
+            if (x, y) == from {
+                return direction(from, xy);
+            }
+            let walkable = matches!(world_data.statics[y][x],
+                                    None | Some(Turret { info: TurretInfo { health: 0, .. } }));
+            if walkable && world_data.mobiles[y][x].is_none() {
+                seen[y][x] = true;
+                queue.push_back((x, y));
+            }
+        }
+    }
+    None
+}
+
+fn direction((x0, y0): (usize, usize), (x, y): (usize, usize)) -> Option<Dir> {
+    match (x as isize - x0 as isize, y as isize - y0 as isize) {
+        (0, -1) => Some(Dir::N),
+        (1, 0) => Some(Dir::E),
+        (0, 1) => Some(Dir::S),
+        (-1, 0) => Some(Dir::W),
+        _ => None,
+    }
+}
@@ -1,14 +1,95 @@
 use model::*;
+use util::*;
+
+use std::cmp::{max, min};
+
+impl ArrowInfo {
+    /// An arrow fired from one place at another.
+    pub fn new(from: (usize, usize),
+               to: (usize, usize),
+               speed: usize,
+               damage_factor: usize,
+               faction: Faction)
+               -> ArrowInfo {
+        let (dx, incx) = make_delta(from.0, to.0);
+        let (dy, incy) = make_delta(from.1, to.1);
+        ArrowInfo {
+            // Vector
+            dx,
+            dy,
+            dir: (incx, incy),
+            incx: if dx > dy {
+                if incx { 1 } else { -1 }
+            } else {
+                0
+            },
+            incy: if dx > dy {
+                0
+            } else {
+                if incy { 1 } else { -1 }
+            },
+            speed,
+            err: if dx > dy {
+                dy as i32 * 2 - dx as i32
+            } else {
+                dx as i32 * 2 - dy as i32
+            },
+            err_inc: if dx > dy {
+                dy as i32 * 2
+            } else {
+                dx as i32 * 2
+            },
+            err_dec: if dx > dy {
+                dx as i32 * 2
+            } else {
+                dy as i32 * 2
+            },
+            corrx: if dx > dy {
+                0
+            } else {
+                if incx { 1 } else { -1 }
+            },
+            corry: if dx > dy {
+                if incy { 1 } else { -1 }
+            } else {
+                0
+            },
+            damage_factor,
+            splash: 0,
+            slow: 0,
+            faction,
+            origin: from,
+        }
+    }
+
+    // Where the arrow goes next from where it is, using Bresenham's
+    // line algorithm.
+    fn advance(&mut self, (x, y): (usize, usize)) -> (usize, usize) {
+        let (mut x, mut y) = (x, y);
+        if self.err >= 0 {
+            self.err -= self.err_dec;
+            x = signed_add(x, self.corrx);
+            y = signed_add(y, self.corry);
+        }
+        self.err += self.err_inc;
+        (signed_add(x, self.incx), signed_add(y, self.incy))
+    }
+}
 
 impl WorldData {
-    pub fn step_arrow(&mut self, (old_x, old_y): (usize, usize), arrow_info: ArrowInfo) {
+    pub fn step_arrow(&mut self, (x, y): (usize, usize), arrow_info: ArrowInfo) {
+        self.mobiles[y][x] = None;
+        self.arrows.remove(&(x, y));
+        self.fly((x, y), arrow_info);
+    }
+
+    /// Move an arrow along from where it is, without disturbing
+    /// anything there (which may be whoever fired it).
+    pub fn fly(&mut self, (old_x, old_y): (usize, usize), arrow_info: ArrowInfo) {
         let mut x = old_x;
         let mut y = old_y;
         let mut info = arrow_info;
 
-        self.mobiles[y][x] = None;
-        self.arrows.remove(&(x, y));
-
         // Would be nice to avoid this extra scope...
         {
             // returns 'true' if movement should continue ('false' if
@@ -16,46 +97,134 @@ impl WorldData {
             //
             // I am not sure why this needs to be mut.
             let mut go = |(x, y): (usize, usize)| {
-                match (self.statics[y][x], self.mobiles[y][x]) {
-                    (Some(Wall), _) => false,
-                    (Some(Gate), _) => false,
-                    (_, Some(Fiend { mut info })) => {
+                match (arrow_info.faction, self.statics[y][x], self.mobiles[y][x]) {
+                    (_, Some(Wall), _) => false,
+                    (_, Some(Gate), _) => false,
+                    (Faction::Defenders, _, Some(Fiend { .. })) => {
+                        self.explode((x, y), arrow_info);
+                        false
+                    }
+                    (Faction::Defenders, Some(Obstacle { .. }), _) => false,
+                    // Fiends don't shoot each other.
+                    (Faction::Fiends, _, Some(Fiend { .. })) => true,
+                    (Faction::Fiends, _, Some(Player)) => {
+                        let damage = resolve_damage(arrow_info.damage_factor,
+                                                    self.player_info.armour_factor);
+                        self.player_info.health = self.player_info.health.saturating_sub(damage);
+                        let player_info = self.player_info;
+                        self.shot("You are", damage, player_info.health, player_info.max_health);
+                        false
+                    }
+                    (Faction::Fiends, Some(Turret { mut info }), _) if info.health > 0 => {
                         info.health = info.health.saturating_sub(arrow_info.damage_factor);
-                        self.shoot(info, arrow_info.damage_factor);
-                        self.mobiles[y][x] = Some(Fiend { info: info });
+                        self.shot("A turret is",
+                                  arrow_info.damage_factor,
+                                  info.health,
+                                  info.max_health);
+                        self.statics[y][x] = Some(Turret { info });
+                        false
+                    }
+                    (Faction::Fiends, Some(Obstacle { health, max_health }), _) => {
+                        let health = health.saturating_sub(arrow_info.damage_factor);
+                        self.shot("An obstacle is", arrow_info.damage_factor, health, max_health);
+                        self.statics[y][x] = Some(Obstacle { health, max_health });
+                        false
+                    }
+                    (Faction::Fiends, Some(Goal { health, max_health }), _) => {
+                        let health = health.saturating_sub(arrow_info.damage_factor);
+                        self.shot("The Thing is", arrow_info.damage_factor, health, max_health);
+                        self.statics[y][x] = Some(Goal { health, max_health });
                         false
                     }
-                    (_, Some(_)) => false,
+                    (_, _, Some(_)) => false,
                     _ => true,
                 }
             };
 
-            // Bresenham's line algorithm
             for _ in 0..info.speed {
-                if info.err >= 0 {
-                    info.err -= info.err_dec;
-                    x = signed_add(x, info.corrx);
-                    y = signed_add(y, info.corry);
-                }
-                info.err += info.err_inc;
-                x = signed_add(x, info.incx);
-                y = signed_add(y, info.incy);
+                (x, y) = info.advance((x, y));
                 if !go((x, y)) {
                     return;
                 }
             }
         }
 
+        // An arrow passing over a fiend can't stop there.
+        if self.mobiles[y][x].is_some() {
+            return;
+        }
         self.arrows.insert((x, y));
-        self.mobiles[y][x] = Some(Arrow { info: info });
+        self.mobiles[y][x] = Some(Arrow { info });
+    }
+
+    /// Whether an arrow fired from one place would get to another,
+    /// rather than hitting something on the way.
+    pub fn line_of_fire(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        let mut info = ArrowInfo::new(from, to, 1, 0, Faction::Defenders);
+        let mut xy = from;
+        for _ in 0..distance(from, to) {
+            xy = info.advance(xy);
+            if xy == to {
+                return true;
+            }
+            match (&self.statics[xy.1][xy.0], &self.mobiles[xy.1][xy.0]) {
+                (Some(Wall), _) | (Some(Gate), _) | (Some(Obstacle { .. }), _) => return false,
+                (_, Some(_)) => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Hit every fiend within the arrow's splash radius of where it
+    /// landed (which is just the one it hit, for most arrows).
+    pub fn explode(&mut self, (x, y): (usize, usize), arrow_info: ArrowInfo) {
+        let r = arrow_info.splash;
+        for fy in y.saturating_sub(r)..min(y + r + 1, self.height()) {
+            for fx in x.saturating_sub(r)..min(x + r + 1, self.width()) {
+                match self.mobiles[fy][fx] {
+                    // Fiends killed earlier this tick are left alone, so
+                    // that they aren't paid out for twice.
+                    Some(Fiend { info: FiendInfo { health: 0, .. } }) => {}
+                    Some(Fiend { mut info }) => {
+                        let damage = resolve_damage(arrow_info.damage_factor,
+                                                    info.armour_factor);
+                        info.health = info.health.saturating_sub(damage);
+                        info.slowed = max(info.slowed, arrow_info.slow);
+                        self.shoot(info, damage, arrow_info.origin);
+                        self.mobiles[fy][fx] = Some(Fiend { info });
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn shot(&mut self, target: &str, damage: usize, health: usize, max_health: usize) {
+        if health == 0 {
+            self.log_msg(format!("{} shot for {} damage! (destroyed!)", target, damage));
+        } else {
+            self.log_msg(format!("{} shot for {} damage! ({} / {})",
+                                 target,
+                                 damage,
+                                 health,
+                                 max_health));
+        }
     }
 
-    fn shoot(&mut self, info: FiendInfo, damage_factor: usize) {
+    // Log a hit on a fiend, and pay out for it (and credit the turret
+    // which fired, if any) if it's dead.
+    fn shoot(&mut self, info: FiendInfo, damage_factor: usize, (x, y): (usize, usize)) {
         if info.health == 0 {
             self.log_msg(format!("{} is shot for {} damage! (dead!)",
                                  info.name,
                                  damage_factor));
             self.cash += info.value;
+            self.kills += 1;
+            if let Some(Turret { mut info }) = self.statics[y][x] {
+                info.kills += 1;
+                self.statics[y][x] = Some(Turret { info });
+            }
         } else {
             self.log_msg(format!("{} is shot for {} damage! ({} / {})",
                                  info.name,
@@ -66,11 +235,19 @@ impl WorldData {
     }
 }
 
+fn make_delta(start: usize, end: usize) -> (usize, bool) {
+    if start < end {
+        (end - start, true)
+    } else {
+        (start - end, false)
+    }
+}
+
 // I felt like making this a macro
 fn signed_add(u: usize, s: i8) -> usize {
     if s < 0 {
-        u.saturating_sub(s.abs() as usize)
+        u.saturating_sub(s.unsigned_abs() as usize)
     } else {
-        u.saturating_add(s.abs() as usize)
+        u.saturating_add(s.unsigned_abs() as usize)
     }
 }
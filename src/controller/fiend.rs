@@ -1,173 +1,299 @@
+use super::flow::FlowFields;
 use model::*;
 use util::*;
 
-use astar::*;
-use std::usize;
-use std::vec::IntoIter;
+// How many ticks ranged fiends take between shots.
+const RELOAD: usize = 4;
+
+// How fast ranged fiends' arrows fly.
+const ARROW_SPEED: usize = 2;
+
+// How much further fiends will go to get around each other.
+const SIDESTEP: usize = 2;
+
+// How many turns fiends with no way to anything wait before they give
+// up and leave, so that the wave can end.
+const GIVE_UP: usize = 50;
 
 impl WorldData {
-    pub fn step_fiend(&mut self, old_xy: (usize, usize), fiend_info: FiendInfo) {
+    pub fn step_fiend(&mut self,
+                      old_xy: (usize, usize),
+                      fiend_info: FiendInfo,
+                      paths: &mut FlowFields) {
         let (old_x, old_y) = old_xy;
+        let mut fiend_info = fiend_info;
+
+        fiend_info.reload = fiend_info.reload.saturating_sub(1);
+        fiend_info.slowed = fiend_info.slowed.saturating_sub(1);
+        self.mobiles[old_y][old_x] = Some(Fiend { info: fiend_info });
+
+        // Slowed fiends lose every other turn.
+        if fiend_info.slowed % 2 == 1 {
+            return;
+        }
+
         let player_xy = self.player_info.location;
-        let goal_xy = (X / 2, Y / 2);
-        let turret_xy = find_nearest(&self.turrets, old_xy);
-        let obstacle_xy = find_nearest(&self.obstacles, old_xy);
+        let target_xy = self.choose_target(old_xy, fiend_info);
 
-        let target_xy = match (turret_xy, obstacle_xy) {
-            _ if distance(old_xy, goal_xy) <= fiend_info.goal_target_distance => {
-                goal_xy // move towards goal
-            }
-            _ if distance(old_xy, player_xy) <= fiend_info.player_target_distance => {
-                player_xy // move towards player
-            }
-            (Some(xy), _) if distance(old_xy, xy) <= fiend_info.turret_target_distance => {
-                xy // move towards turret
-            }
-            (_, Some(xy)) if distance(old_xy, xy) <= fiend_info.obstacle_target_distance => {
-                xy // move towards obstacle
+        // Ranged fiends stop once they're close enough to shoot what
+        // they're after, unless it's just an obstacle in the way.
+        let (target_x, target_y) = target_xy;
+        let shootable = match self.statics[target_y][target_x] {
+            _ if self.player_in_room && target_xy == player_xy => true,
+            Some(Goal { .. }) => true,
+            Some(Turret { info }) => info.health > 0,
+            _ => false,
+        };
+        if shootable && distance(old_xy, target_xy) <= fiend_info.archetype.range() {
+            if fiend_info.reload == 0 {
+                fiend_info.reload = RELOAD;
+                self.mobiles[old_y][old_x] = Some(Fiend { info: fiend_info });
+                let arrow = ArrowInfo::new(old_xy,
+                                           target_xy,
+                                           ARROW_SPEED,
+                                           fiend_info.damage_factor,
+                                           Faction::Fiends);
+                self.fly(old_xy, arrow);
             }
-            _ => {
-                goal_xy // move towards goal if no better options
+            return;
+        }
+
+        // Find the next step, going after something else if there's no
+        // way to the target, or waiting if there's no way anywhere.
+        let (target_xy, (new_x, new_y)) = match self.route(old_xy, target_xy, fiend_info, paths) {
+            Some(route) => route,
+            None => {
+                self.wait(old_xy, fiend_info);
+                return;
             }
         };
+        if fiend_info.stuck != 0 {
+            fiend_info.stuck = 0;
+            self.mobiles[old_y][old_x] = Some(Fiend { info: fiend_info });
+        }
 
-        // Find the next step.
-        let (new_x, new_y) = self.pathfind(old_xy, target_xy, fiend_info.damage_factor);
-
+        // Flying fiends go over anything they aren't attacking.
+        let flies_over = fiend_info.form == FiendForm::Flying && (new_x, new_y) != target_xy;
         match self.statics[new_y][new_x] {
+            Some(Wall) if fiend_info.form == FiendForm::Tunnelling => {}
             Some(Wall) => return,
+            Some(Obstacle { .. }) | Some(Turret { .. }) if flies_over => {}
             Some(Obstacle { health, max_health }) => {
-                let new_health = health.saturating_sub(fiend_info.damage_factor);
+                let damage = fiend_info.damage_factor;
+                let new_health = health.saturating_sub(damage);
                 self.statics[new_y][new_x] = Some(Obstacle {
                     health: new_health,
-                    max_health: max_health,
+                    max_health,
                 });
-                self.fiend_hit("an obstacle", fiend_info, new_health, max_health);
+                self.fiend_hit("an obstacle", fiend_info, damage, new_health, max_health);
                 return;
             }
             Some(Goal { health, max_health }) => {
-                let new_health = health.saturating_sub(fiend_info.damage_factor);
+                let damage = fiend_info.damage_factor;
+                let new_health = health.saturating_sub(damage);
                 self.statics[new_y][new_x] = Some(Goal {
                     health: new_health,
-                    max_health: max_health,
+                    max_health,
                 });
-                self.fiend_hit("the Thing", fiend_info, new_health, max_health);
+                self.fiend_hit("the Thing", fiend_info, damage, new_health, max_health);
                 return;
             }
             Some(Turret { mut info }) if info.health > 0 => {
-                info.health = info.health.saturating_sub(fiend_info.damage_factor);
-                self.fiend_hit("a turret", fiend_info, info.health, info.max_health);
-                self.statics[new_y][new_x] = Some(Turret { info: info });
+                let damage = fiend_info.damage_factor;
+                info.health = info.health.saturating_sub(damage);
+                self.fiend_hit("a turret", fiend_info, damage, info.health, info.max_health);
+                self.statics[new_y][new_x] = Some(Turret { info });
                 return;
             }
             _ => {} // we can move into an empty space, and also broken turrets and gates.
         };
         match self.mobiles[new_y][new_x] {
-            Some(Arrow { .. }) => return, // TODO: be damaged
-            Some(Fiend { .. }) => return, // TODO: try moving elsewhere
+            Some(Arrow { info }) if info.faction == Faction::Defenders => {
+                // Walking into an arrow is as bad as being hit by it.
+                self.arrows.remove(&(new_x, new_y));
+                self.mobiles[new_y][new_x] = None;
+                self.explode(old_xy, info);
+                return;
+            }
+            Some(Arrow { .. }) => return, // fiends' arrows fly past fiends, so wait for it
+            Some(Fiend { info }) => {
+                self.make_way(old_xy, (new_x, new_y), target_xy, fiend_info, info, paths);
+                return;
+            }
             Some(Player) => {
-                self.player_info.health =
-                    self.player_info.health.saturating_sub(fiend_info.damage_factor);
+                let damage = resolve_damage(fiend_info.damage_factor,
+                                            self.player_info.armour_factor);
+                self.player_info.health = self.player_info.health.saturating_sub(damage);
                 let player_info = self.player_info;
                 self.fiend_hit("you",
                                fiend_info,
+                               damage,
                                player_info.health,
                                player_info.max_health);
                 return;
             }
             None => {} // we can move into an empty space
         }
-        self.fiends.remove(&(old_x, old_y));
-        self.fiends.insert((new_x, new_y));
-        self.mobiles[old_y][old_x] = None;
-        self.mobiles[new_y][new_x] = Some(Fiend { info: fiend_info });
+        self.move_fiend(old_xy, (new_x, new_y), fiend_info);
     }
 
-    // A* search, with the following special costs:
-    //
-    // - The cost of walking through an obstacle or turret is 1 + the
-    //   number of turns taken to destroy it
-    //
-    //  - The cost of walking through another fiend is 2 (1 turn for
-    //  it to move away, then 1 turn to move to the space).
+    // Wait for a way to open up, or leave the room after waiting too
+    // long. Fiends which leave aren't killed, so nobody gets paid.
+    fn wait(&mut self, (x, y): (usize, usize), fiend_info: FiendInfo) {
+        let mut fiend_info = fiend_info;
+        fiend_info.stuck += 1;
+        if fiend_info.stuck < GIVE_UP {
+            self.mobiles[y][x] = Some(Fiend { info: fiend_info });
+            return;
+        }
+        self.mobiles[y][x] = None;
+        self.fiends.remove(&(x, y));
+        self.log_msg(format!("{} can't get anywhere, and leaves.", fiend_info.name));
+    }
+
+    // Get a fiend past another one in its way, by swapping places if
+    // the other one has already had its turn and doesn't mind, or by
+    // stepping around it if that's not much further. Otherwise it waits.
     //
-    // Returns the first step along the path.
-    fn pathfind(&self,
+    // Fiends take turns in order of where they are, so only swapping
+    // with fiends earlier in that order means nobody moves twice.
+    fn make_way(&mut self,
                 my_xy: (usize, usize),
+                their_xy: (usize, usize),
                 target_xy: (usize, usize),
-                damage_factor: usize)
-                -> (usize, usize) {
-        let mut searcher = WorldSearch {
-            world_data: self,
-            start: my_xy,
-            end: target_xy,
-            damage_factor: damage_factor,
+                fiend_info: FiendInfo,
+                their_info: FiendInfo,
+                paths: &mut FlowFields) {
+        let their_target = self.choose_target(their_xy, their_info);
+        let gain = (paths.distance(self, their_xy, target_xy, fiend_info),
+                    paths.distance(self, my_xy, target_xy, fiend_info));
+        let loss = (paths.distance(self, my_xy, their_target, their_info),
+                    paths.distance(self, their_xy, their_target, their_info));
+        let willing = match (gain, loss) {
+            ((Some(mine_after), Some(mine_before)), (Some(theirs_after), Some(theirs_before))) => {
+                mine_after < mine_before && theirs_after <= theirs_before
+            }
+            _ => false,
         };
-        let path = astar(&mut searcher);
-        *path.expect("No path found!").get(1).expect("No path found!")
+        if their_xy < my_xy && willing && self.can_stand(my_xy, their_info.form) {
+            self.mobiles[their_xy.1][their_xy.0] = Some(Fiend { info: fiend_info });
+            self.mobiles[my_xy.1][my_xy.0] = Some(Fiend { info: their_info });
+            return;
+        }
+
+        let steps = paths.steps(self, my_xy, target_xy, fiend_info);
+        let best = steps.first().map_or(0, |&(_, total)| total);
+        let around = steps.into_iter()
+            .take_while(|&(_, total)| total <= best.saturating_add(SIDESTEP))
+            .map(|(xy, _)| xy)
+            .find(|&xy| self.mobiles[xy.1][xy.0].is_none() && self.can_stand(xy, fiend_info.form));
+        if let Some(xy) = around {
+            self.move_fiend(my_xy, xy, fiend_info);
+        }
+    }
+
+    // Whether a fiend can be in a space without having to break
+    // anything.
+    fn can_stand(&self, (x, y): (usize, usize), form: FiendForm) -> bool {
+        match self.statics[y][x] {
+            None | Some(Gate) => true,
+            Some(Turret { info }) if info.health == 0 => true,
+            Some(Wall) => form == FiendForm::Tunnelling,
+            Some(Obstacle { .. }) | Some(Turret { .. }) => form == FiendForm::Flying,
+            Some(Goal { .. }) => false,
+        }
+    }
+
+    fn move_fiend(&mut self, from: (usize, usize), to: (usize, usize), fiend_info: FiendInfo) {
+        self.fiends.remove(&from);
+        self.fiends.insert(to);
+        self.mobiles[from.1][from.0] = None;
+        self.mobiles[to.1][to.0] = Some(Fiend { info: fiend_info });
+    }
+
+    // What a fiend goes after, depending on what's nearby.
+    fn choose_target(&self, my_xy: (usize, usize), fiend_info: FiendInfo) -> (usize, usize) {
+        let player_xy = self.player_info.location;
+        let goal_xy = self.goal_location;
+        let turret_xy = find_nearest(&self.turrets, my_xy);
+        let obstacle_xy = find_nearest(&self.obstacles, my_xy);
+
+        match (turret_xy, obstacle_xy) {
+            _ if distance(my_xy, goal_xy) <= fiend_info.goal_target_distance => {
+                goal_xy // move towards goal
+            }
+            _ if self.player_in_room &&
+                 distance(my_xy, player_xy) <= fiend_info.player_target_distance => {
+                player_xy // move towards player
+            }
+            (Some(xy), _) if distance(my_xy, xy) <= fiend_info.turret_target_distance => {
+                xy // move towards turret
+            }
+            (_, Some(xy)) if distance(my_xy, xy) <= fiend_info.obstacle_target_distance => {
+                xy // move towards obstacle
+            }
+            _ => {
+                goal_xy // move towards goal if no better options
+            }
+        }
+    }
+
+    // The next step towards the target, if the fiend can get there.
+    // Otherwise, the first other thing worth attacking it can get to
+    // (the Thing, the player, then turrets and obstacles from nearest
+    // to furthest) and the next step towards that.
+    //
+    // Anything but a wall can be broken through, so this only happens
+    // when the fiend is walled in or the target is walled off.
+    fn route(&self,
+             my_xy: (usize, usize),
+             target_xy: (usize, usize),
+             fiend_info: FiendInfo,
+             paths: &mut FlowFields)
+             -> Option<((usize, usize), (usize, usize))> {
+        if let Some(step) = paths.next_step(self, my_xy, target_xy, fiend_info) {
+            return Some((target_xy, step));
+        }
+
+        let mut structures = self.turrets
+            .iter()
+            .filter(|&&(x, y)| match self.statics[y][x] {
+                Some(Turret { info }) => info.health > 0,
+                _ => false,
+            })
+            .chain(self.obstacles.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        structures.sort_by_key(|&xy| distance(my_xy, xy));
+
+        let mut targets = vec![self.goal_location];
+        if self.player_in_room {
+            targets.push(self.player_info.location);
+        }
+        targets.extend(structures);
+        targets.into_iter()
+            .filter_map(|xy| Some((xy, paths.next_step(self, my_xy, xy, fiend_info)?)))
+            .next()
     }
 
     fn fiend_hit(&mut self,
                  target: &str,
                  fiend_info: FiendInfo,
+                 damage: usize,
                  health: usize,
                  max_health: usize) {
         if health == 0 {
             self.log_msg(format!("{} hits {} for {} damage! (destroyed!)",
                                  fiend_info.name,
                                  target,
-                                 fiend_info.damage_factor));
+                                 damage));
         } else {
             self.log_msg(format!("{} hits {} for {} damage! ({} / {})",
                                  fiend_info.name,
                                  target,
-                                 fiend_info.damage_factor,
+                                 damage,
                                  health,
                                  max_health));
         }
     }
 }
-
-// A* implementation
-struct WorldSearch<'a> {
-    world_data: &'a WorldData,
-    start: (usize, usize),
-    end: (usize, usize),
-    damage_factor: usize,
-}
-
-impl<'a> SearchProblem for WorldSearch<'a> {
-    type Node = (usize, usize);
-    type Cost = usize;
-    type Iter = IntoIter<((usize, usize), usize)>;
-
-    fn start(&self) -> (usize, usize) {
-        self.start
-    }
-    fn is_end(&self, p: &(usize, usize)) -> bool {
-        *p == self.end
-    }
-    fn heuristic(&self, &(p_x, p_y): &(usize, usize)) -> usize {
-        let (s_x, s_y) = self.end;
-        (s_x.saturating_sub(p_x)).saturating_add(s_y.saturating_sub(p_y))
-    }
-    fn neighbors(&mut self, position: &(usize, usize)) -> IntoIter<((usize, usize), usize)> {
-        let mut vec = vec![];
-        for (x, y) in adjacency(*position) {
-            let mcost = match (self.world_data.statics[y][x], self.world_data.mobiles[y][x]) {
-                (Some(Wall), _) => None,
-                (Some(Turret { info: TurretInfo { health, .. } }), _) |
-                (Some(Obstacle { health, .. }), _) |
-                (Some(Goal { health, .. }), _) => Some(health / self.damage_factor),
-                (_, Some(Player)) => Some(self.world_data.player_info.health / self.damage_factor),
-                (_, Some(Fiend { .. })) => Some(1),
-                _ => Some(0),
-            };
-            match mcost {
-                Some(cost) => vec.push(((x, y), cost.saturating_add(1))),
-                None => {}
-            }
-        }
-        vec.into_iter()
-    }
-}
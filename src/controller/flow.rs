@@ -0,0 +1,495 @@
+use model::*;
+use util::adjacency;
+
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+use std::mem;
+
+// Everything a flow field depends on, other than the world itself.
+//
+// How long a fiend takes to break through something depends on how
+// hard it hits, so fiends are grouped into bands of damage within a
+// factor of two of each other, which share a field.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct FieldKey {
+    target: (usize, usize),
+    form: FiendForm,
+    band: u32,
+}
+
+impl FieldKey {
+    fn new(target: (usize, usize), fiend_info: FiendInfo) -> FieldKey {
+        FieldKey {
+            target,
+            form: fiend_info.form,
+            band: max(1, fiend_info.damage_factor).ilog2(),
+        }
+    }
+
+    // The damage every fiend in the band is assumed to do.
+    fn damage(&self) -> usize {
+        1 << self.band
+    }
+}
+
+/// The distance from every space to a target, computed the first time
+/// a fiend asks about it and then shared with every other fiend after
+/// the same thing. Fields are kept from tick to tick, until the room
+/// changes in a way which could change them.
+#[derive(Default)]
+pub struct FlowFields {
+    // The room the fields were worked out for, and what in it can
+    // change.
+    room: Option<Grid<Space>>,
+    things: Vec<((usize, usize), Space)>,
+    fields: BTreeMap<FieldKey, Field>,
+}
+
+impl FlowFields {
+    pub fn new() -> FlowFields {
+        FlowFields {
+            room: None,
+            things: Vec::new(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Catch up with changes to the room since the fields were worked
+    /// out. Fields are brought up to date if things only got easier to
+    /// get through, and thrown away otherwise. This needs doing before
+    /// fiends move each tick.
+    pub fn update(&mut self, world_data: &WorldData) {
+        let things = Space::things(world_data);
+        if things == self.things {
+            return;
+        }
+        let easier = things.len() == self.things.len() &&
+                     things.iter().zip(&self.things).all(|(&(xy, new), &(old_xy, old))| {
+            xy == old_xy && (new == old || new.is_easier_than(old))
+        });
+        match self.room {
+            Some(ref mut room) if easier => {
+                for (&(xy, new), &(_, old)) in things.iter().zip(&self.things) {
+                    if new != old {
+                        room[xy.1][xy.0] = new;
+                        for field in self.fields.values_mut() {
+                            field.changed.push(xy);
+                        }
+                    }
+                }
+            }
+            _ => {
+                self.room = None;
+                self.fields.clear();
+            }
+        }
+        self.things = things;
+    }
+
+    /// The next step for a fiend on the cheapest path to its target,
+    /// or None if there's no path.
+    pub fn next_step(&mut self,
+                     world_data: &WorldData,
+                     from: (usize, usize),
+                     target: (usize, usize),
+                     fiend_info: FiendInfo)
+                     -> Option<(usize, usize)> {
+        self.steps(world_data, from, target, fiend_info).first().map(|&(to, _)| to)
+    }
+
+    /// Every step a fiend can take towards its target, with the cost
+    /// of the whole path that way, cheapest first.
+    ///
+    /// Fields leave fiends out, so the fiends in the way are counted
+    /// for this step and the one after, the one asking included. That
+    /// way fiends getting around each other don't step back the way
+    /// they came.
+    pub fn steps(&mut self,
+                 world_data: &WorldData,
+                 from: (usize, usize),
+                 target: (usize, usize),
+                 fiend_info: FiendInfo)
+                 -> Vec<((usize, usize), usize)> {
+        let key = FieldKey::new(target, fiend_info);
+        let terrain = Terrain::new(world_data, key);
+        let field = self.field(world_data, key);
+
+        let mut steps = Vec::new();
+        terrain.each_step(field, from, |xy, total| {
+            if xy == target {
+                steps.push((xy, total));
+                return;
+            }
+            let mut onward = None;
+            terrain.each_step(field, xy, |_, next| {
+                onward = Some(onward.map_or(next, |best| min(best, next)));
+            });
+            if let (Some(dist), Some(onward)) = (field.get(xy), onward) {
+                steps.push((xy, total - dist + onward));
+            }
+        });
+        steps.sort_by_key(|&(_, total)| total);
+        steps
+    }
+
+    /// The cost of the cheapest path from a space to a fiend's target,
+    /// or None if there's no path.
+    pub fn distance(&mut self,
+                    world_data: &WorldData,
+                    from: (usize, usize),
+                    target: (usize, usize),
+                    fiend_info: FiendInfo)
+                    -> Option<usize> {
+        self.field(world_data, FieldKey::new(target, fiend_info)).get(from)
+    }
+
+    // The field for a key, working it out if nobody has asked for it
+    // since it was last thrown away.
+    fn field(&mut self, world_data: &WorldData, key: FieldKey) -> &Field {
+        let room = self.room.get_or_insert_with(|| Space::room(world_data));
+        let field = self.fields.entry(key).or_insert_with(|| Field::new(room, key));
+        if !field.changed.is_empty() {
+            field.lower(room, key);
+        }
+        field
+    }
+}
+
+// What a space is like to get through, as far as flow fields go.
+// Fiends and arrows are left out, as they move about every tick, so
+// fields only count them for the step a fiend is about to take.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Space {
+    Open,
+    Wall,
+    // Things which can be broken through, with their health.
+    Obstacle(usize),
+    Turret(usize),
+    Goal(usize),
+    Player(usize),
+}
+
+impl Space {
+    fn new(sta: &Option<Static>, mob: &Option<Mobile>, player_info: &PlayerInfo) -> Space {
+        match (sta, mob) {
+            (Some(Wall), _) => Space::Wall,
+            (Some(Obstacle { health, .. }), _) => Space::Obstacle(*health),
+            (Some(Turret { info }), _) => Space::Turret(info.health),
+            (Some(Goal { health, .. }), _) => Space::Goal(*health),
+            (_, Some(Player)) => Space::Player(player_info.health),
+            _ => Space::Open,
+        }
+    }
+
+    fn at(world_data: &WorldData, (x, y): (usize, usize)) -> Space {
+        Space::new(&world_data.statics[y][x],
+                   &world_data.mobiles[y][x],
+                   &world_data.player_info)
+    }
+
+    fn room(world_data: &WorldData) -> Grid<Space> {
+        let mut room = Grid::new(world_data.width(), world_data.height(), Space::Open);
+        let rows = world_data.statics.rows().zip(world_data.mobiles.rows());
+        for (y, (statics, mobiles)) in rows.enumerate() {
+            for (x, (sta, mob)) in statics.iter().zip(mobiles).enumerate() {
+                room[y][x] = Space::new(sta, mob, &world_data.player_info);
+            }
+        }
+        room
+    }
+
+    // Whether getting through the space has got no harder for any
+    // fiend since it was the other space. Fiends which could leap over
+    // something which has gone can walk through it in the same time.
+    fn is_easier_than(self, old: Space) -> bool {
+        match (old, self) {
+            (Space::Obstacle(old), Space::Obstacle(new)) |
+            (Space::Turret(old), Space::Turret(new)) |
+            (Space::Goal(old), Space::Goal(new)) |
+            (Space::Player(old), Space::Player(new)) => new <= old,
+            (Space::Obstacle(_), Space::Open) |
+            (Space::Turret(_), Space::Open) => true,
+            _ => false,
+        }
+    }
+
+    // Everything which can change while fiends are finding their way
+    // around: the Thing, the player, turrets and obstacles. Walls and
+    // gates stay put, and fiends and arrows aren't part of the room.
+    fn things(world_data: &WorldData) -> Vec<((usize, usize), Space)> {
+        let mut things = vec![world_data.goal_location];
+        if world_data.player_in_room {
+            things.push(world_data.player_info.location);
+        }
+        things.extend(&world_data.turrets);
+        things.extend(&world_data.obstacles);
+        things.into_iter().map(|xy| (xy, Space::at(world_data, xy))).collect()
+    }
+
+    // The cost of moving into the space, or None if it can't be
+    // entered. Flying fiends only stop for obstacles and turrets
+    // they're after.
+    fn cost(self, form: FiendForm, damage: usize, is_target: bool) -> Option<usize> {
+        let flies_over = form == FiendForm::Flying && !is_target;
+        match self {
+            Space::Wall if form == FiendForm::Tunnelling => Some(1),
+            Space::Wall => None,
+            Space::Obstacle(_) | Space::Turret(_) if flies_over => Some(0),
+            Space::Obstacle(health) |
+            Space::Turret(health) |
+            Space::Goal(health) |
+            Space::Player(health) => Some(health / damage),
+            Space::Open => Some(0),
+        }
+    }
+
+    // Whether a leaping fiend can land in the space, if nobody else is
+    // there.
+    fn can_land(self) -> bool {
+        matches!(self, Space::Open | Space::Turret(0))
+    }
+
+    fn can_leap_over(self, form: FiendForm) -> bool {
+        form == FiendForm::Leaping && matches!(self, Space::Obstacle(_) | Space::Turret(_))
+    }
+}
+
+// The costs of getting around, with the following special cases:
+//
+// - The cost of walking through an obstacle or turret is 1 + the
+//   number of turns taken to destroy it
+//
+//  - The cost of walking through another fiend is 2 (1 turn for
+//  it to move away, then 1 turn to move to the space).
+//
+// Flying fiends can pass over obstacles and turrets for free,
+// tunnelling fiends can pass through walls, and leaping fiends can
+// jump over an obstacle or turret to the empty space behind it in
+// a single step.
+//
+// This is the room as it is right now, fiends and all, for the step a
+// fiend is about to take.
+struct Terrain<'a> {
+    world_data: &'a WorldData,
+    target: (usize, usize),
+    damage: usize,
+    form: FiendForm,
+}
+
+impl<'a> Terrain<'a> {
+    fn new(world_data: &'a WorldData, key: FieldKey) -> Terrain<'a> {
+        Terrain {
+            world_data,
+            target: key.target,
+            damage: key.damage(),
+            form: key.form,
+        }
+    }
+
+    // The cost of moving into a space, or None if it can't be entered.
+    fn cost(&self, (x, y): (usize, usize)) -> Option<usize> {
+        match Space::at(self.world_data, (x, y)) {
+            Space::Open if matches!(self.world_data.mobiles[y][x], Some(Fiend { .. })) => Some(1),
+            space => space.cost(self.form, self.damage, (x, y) == self.target),
+        }
+    }
+
+    // Every step from a space, with the cost of the whole path to the
+    // field's target that way.
+    fn each_step<F>(&self, field: &Field, from: (usize, usize), mut f: F)
+        where F: FnMut((usize, usize), usize)
+    {
+        let size = (self.world_data.width(), self.world_data.height());
+        for xy in adjacency(from, size) {
+            if let (Some(cost), Some(dist)) = (self.cost(xy), field.get(xy)) {
+                f(xy, dist.saturating_add(cost).saturating_add(1));
+            }
+            if let Some(landing) = self.leap(from, xy) {
+                if let Some(dist) = field.get(landing) {
+                    f(landing, dist.saturating_add(2));
+                }
+            }
+        }
+    }
+
+    // Where a leaping fiend lands if it jumps from one space over the
+    // next, if it can.
+    fn leap(&self, (x0, y0): (usize, usize), (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        if !Space::at(self.world_data, (x, y)).can_leap_over(self.form) {
+            return None;
+        }
+        let landing = ((2 * x).checked_sub(x0)?, (2 * y).checked_sub(y0)?);
+        if self.world_data.mobiles.contains(landing) &&
+           self.world_data.mobiles[landing.1][landing.0].is_none() &&
+           Space::at(self.world_data, landing).can_land() {
+            Some(landing)
+        } else {
+            None
+        }
+    }
+}
+
+// The cost of the cheapest path to a target from every space in the
+// room, or None from spaces with no path. Spaces are numbered along
+// each row in turn, as that's quicker to work with than coordinates.
+struct Field {
+    width: usize,
+    // Distances are kept small, so that a field fits in the cache,
+    // with 'UNREACHABLE' for spaces with no path.
+    dists: Vec<u32>,
+    // Spaces which have got easier to get through since the field was
+    // last asked for.
+    changed: Vec<(usize, usize)>,
+}
+
+const UNREACHABLE: u32 = u32::MAX;
+
+// Add a cost to a distance, stopping short of 'UNREACHABLE' however
+// far off things get.
+fn add(dist: u32, cost: usize) -> u32 {
+    min((dist as usize).saturating_add(cost), UNREACHABLE as usize - 1) as u32
+}
+
+impl Field {
+    fn new(room: &Grid<Space>, key: FieldKey) -> Field {
+        let mut field = Field {
+            width: room.width(),
+            dists: vec![UNREACHABLE; room.width() * room.height()],
+            changed: Vec::new(),
+        };
+        field.dists[key.target.1 * field.width + key.target.0] = 0;
+        let mut queue = Queue::new();
+        queue.push(key.target, 0);
+        field.expand(room, key, queue);
+        field
+    }
+
+    // Bring the field up to date after spaces got easier to get
+    // through, by finding cheaper paths through them.
+    fn lower(&mut self, room: &Grid<Space>, key: FieldKey) {
+        let mut queue = Queue::new();
+        for (x, y) in mem::take(&mut self.changed) {
+            let dist = self.dists[y * self.width + x];
+            if dist != UNREACHABLE {
+                queue.push((x, y), dist);
+            }
+        }
+        self.expand(room, key, queue);
+    }
+
+    // Dijkstra's algorithm, working outwards from the spaces in the
+    // queue along every move in reverse, until there's nowhere left
+    // which can be got to more cheaply.
+    fn expand(&mut self, room: &Grid<Space>, key: FieldKey, mut queue: Queue) {
+        let (width, height) = (room.width(), room.height());
+        let mut bucket = Vec::new();
+        while let Some(dist) = queue.pop(&mut bucket) {
+            for &(x, y) in &bucket {
+                if self.dists[y * width + x] < dist {
+                    continue;
+                }
+                let dists = &mut self.dists;
+                let mut relax = |(x, y): (usize, usize), new_dist: u32| {
+                    let i = y * width + x;
+                    if new_dist < dists[i] {
+                        dists[i] = new_dist;
+                        queue.push((x, y), new_dist);
+                    }
+                };
+
+                // Stepping into this space from next door.
+                if let Some(cost) = room[y][x].cost(key.form, key.damage(), (x, y) == key.target) {
+                    let new_dist = add(dist, cost.saturating_add(1));
+                    for from in adjacency((x, y), (width, height)) {
+                        relax(from, new_dist);
+                    }
+                }
+
+                // Leaping into this space over something next door.
+                if key.form != FiendForm::Leaping || !room[y][x].can_land() {
+                    continue;
+                }
+                for (ox, oy) in adjacency((x, y), (width, height)) {
+                    if !room[oy][ox].can_leap_over(key.form) {
+                        continue;
+                    }
+                    let from = ((2 * ox).checked_sub(x), (2 * oy).checked_sub(y));
+                    if let (Some(fx), Some(fy)) = from {
+                        if fx < width && fy < height {
+                            relax((fx, fy), add(dist, 2));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn get(&self, (x, y): (usize, usize)) -> Option<usize> {
+        match self.dists[y * self.width + x] {
+            UNREACHABLE => None,
+            dist => Some(dist as usize),
+        }
+    }
+}
+
+// How many distances past the one being expanded the ring of buckets
+// in a queue covers.
+const RING: u32 = 64;
+
+// Spaces waiting to be expanded, by distance. Costs are whole numbers,
+// and most moves cost one or two, so spaces are kept in buckets rather
+// than a heap, which is a lot quicker. The next few distances are in a
+// ring of buckets, and only spaces further off than that, behind
+// things which take a long time to break, go in a map.
+struct Queue {
+    ring: Vec<Vec<(usize, usize)>>,
+    in_ring: usize,
+    far: BTreeMap<u32, Vec<(usize, usize)>>,
+    // The next distance to expand.
+    dist: u32,
+}
+
+impl Queue {
+    fn new() -> Queue {
+        Queue {
+            ring: vec![Vec::new(); RING as usize],
+            in_ring: 0,
+            far: BTreeMap::new(),
+            dist: 0,
+        }
+    }
+
+    // Nothing can be pushed nearer than the last distance popped, which
+    // Dijkstra's algorithm never does.
+    fn push(&mut self, xy: (usize, usize), dist: u32) {
+        if dist - self.dist < RING {
+            self.ring[(dist % RING) as usize].push(xy);
+            self.in_ring += 1;
+        } else {
+            self.far.entry(dist).or_default().push(xy);
+        }
+    }
+
+    // Fill the bucket with every space at the nearest distance there
+    // are any, and return that distance, or None if the queue is empty.
+    fn pop(&mut self, bucket: &mut Vec<(usize, usize)>) -> Option<u32> {
+        loop {
+            if self.in_ring == 0 {
+                self.dist = *self.far.keys().next()?;
+            }
+            let dist = self.dist;
+            self.dist += 1;
+            let far = self.far.keys().next() == Some(&dist);
+            if self.ring[(dist % RING) as usize].is_empty() && !far {
+                continue;
+            }
+            bucket.clear();
+            mem::swap(bucket, &mut self.ring[(dist % RING) as usize]);
+            self.in_ring -= bucket.len();
+            if far {
+                bucket.extend(self.far.remove(&dist).unwrap_or_default());
+            }
+            return Some(dist);
+        }
+    }
+}
@@ -1,41 +1,36 @@
 mod arrow;
 mod fiend;
+mod flow;
 mod turret;
 
+pub use self::flow::FlowFields;
 use model::*;
 use fiends::make_wave;
+use util::resolve_damage;
 
-use pancurses::Input;
-use pancurses::Input::*;
+use model::Command::*;
 
-use rand::{Rng, thread_rng};
+use rand::Rng;
 
 use std::cmp::min;
 use std::collections::BTreeSet;
 use std::mem;
 
-enum Dir {
-    N,
-    E,
-    S,
-    W,
-}
-
 impl Static {
     fn player_interact(&mut self, player_info: &PlayerInfo) {
         match *self {
             Wall | Gate => {}
-            Obstacle { mut health, max_health } |
-            Goal { mut health, max_health } |
-            Turret { info: TurretInfo { mut health, max_health, .. } } => {
-                health = min(health + player_info.heal_factor, max_health)
+            Obstacle { ref mut health, max_health } |
+            Goal { ref mut health, max_health } |
+            Turret { info: TurretInfo { ref mut health, max_health, .. } } => {
+                *health = min(*health + player_info.heal_factor, max_health)
             }
         };
     }
 }
 
 impl GameState {
-    pub fn handle(&mut self, world_data: &mut WorldData, i: Input) {
+    pub fn handle(&mut self, world_data: &mut WorldData, command: Command) {
         match *self {
             Startup => {
                 *self = Construct {
@@ -43,66 +38,93 @@ impl GameState {
                     menu_index: 0,
                 }
             }
-            Construct { .. } => self.construct_handler(world_data, i),
-            Fight { .. } => self.fight_handler(world_data, i),
-            GameOver { .. } => self.gameover_handler(i),
+            Construct { .. } => self.construct_handler(world_data, command),
+            Fight { .. } => self.fight_handler(world_data, command),
+            GameOver { .. } => self.gameover_handler(command),
             End => panic!("Should have ended and didn't!"),
         };
     }
 
-    fn construct_handler(&mut self, world_data: &mut WorldData, i: Input) {
+    fn construct_handler(&mut self, world_data: &mut WorldData, command: Command) {
         let (menu, index) = match *self {
             Construct { menu: m, menu_index: i } => (m, i),
             _ => unreachable!(),
         };
 
+        // The direct commands work from any menu, so long as there
+        // isn't something half-way through being moved.
+        match (menu, command) {
+            (Menu::Place(..), Build(..)) |
+            (Menu::Place(..), Place(..)) |
+            (Menu::Place(..), Continue) => return,
+            (_, Build(buildable, location)) => {
+                world_data.build(buildable.placement(), location, buildable.cost());
+                return;
+            }
+            (_, Place(from, to)) => {
+                world_data.relocate(from, to);
+                return;
+            }
+            (_, Continue) => {
+                self.start_fight(world_data);
+                return;
+            }
+            _ => {}
+        }
+
         match menu {
-            Menu::Place(placement, location) => {
-                match i {
-                    KeyDown | Character('s') => {
+            Menu::Place(placement, location, cost) => {
+                match command {
+                    Move(Dir::S) => {
                         let new_y = location.1 + 1;
                         *self = Construct {
                             menu: Menu::Place(placement,
                                               (location.0,
-                                               if new_y == Y - 1 { Y - 2 } else { new_y })),
+                                               min(new_y, world_data.height() - 2)),
+                                              cost),
                             menu_index: 0,
                         };
                     }
-                    KeyUp | Character('w') => {
+                    Move(Dir::N) => {
                         let new_y = location.1 - 1;
                         *self = Construct {
                             menu: Menu::Place(placement,
-                                              (location.0, if new_y == 0 { 1 } else { new_y })),
+                                              (location.0, if new_y == 0 { 1 } else { new_y }),
+                                              cost),
                             menu_index: 0,
                         };
                     }
-                    KeyLeft | Character('a') => {
+                    Move(Dir::W) => {
                         let new_x = location.0 - 1;
                         *self = Construct {
                             menu: Menu::Place(placement,
-                                              (if new_x == 0 { 1 } else { new_x }, location.1)),
+                                              (if new_x == 0 { 1 } else { new_x }, location.1),
+                                              cost),
                             menu_index: 0,
                         };
                     }
-                    KeyRight | Character('d') => {
+                    Move(Dir::E) => {
                         let new_x = location.0 + 1;
                         *self = Construct {
                             menu: Menu::Place(placement,
-                                              (if new_x == X - 1 { X - 2 } else { new_x },
-                                               location.1)),
+                                              (min(new_x, world_data.width() - 2), location.1),
+                                              cost),
                             menu_index: 0,
                         };
                     }
-                    Character(' ') | Character('\n') => {
-                        if world_data.statics[location.1][location.0].is_some() {
+                    Aim => {
+                        if let Turret { mut info } = placement {
+                            info.targeting = info.targeting.next();
+                            *self = Construct {
+                                menu: Menu::Place(Turret { info }, location, cost),
+                                menu_index: 0,
+                            };
+                        }
+                    }
+                    Select => {
+                        if !world_data.build(placement, location, cost) {
                             return;
                         }
-                        world_data.statics[location.1][location.0] = Some(placement);
-                        match placement {
-                            Turret { .. } => world_data.turrets.insert(location),
-                            Obstacle { .. } => world_data.obstacles.insert(location),
-                            _ => panic!("Placing the unplaceable?"),
-                        };
                         *self = Construct {
                             menu: Menu::Root,
                             menu_index: 0,
@@ -111,187 +133,146 @@ impl GameState {
                     _ => {}
                 }
             }
-            Menu::Move(depth) => {
-                let y = Y + 5 + 7 - 5 - 1;
-                match i {
-                    KeyDown | Character('s') => {
-                        if index == y {
-                            if depth + y == world_data.turrets.len() + world_data.obstacles.len() {
-                                *self = Construct {
-                                    menu: Menu::Move(0),
-                                    menu_index: 0,
-                                };
-                            } else {
-                                *self = Construct {
-                                    menu: Menu::Move(depth + 1),
-                                    menu_index: index,
-                                };
-                            }
-                        } else {
-                            if index + depth ==
-                               world_data.turrets.len() + world_data.obstacles.len() {
-                                *self = Construct {
-                                    menu: Menu::Move(0),
-                                    menu_index: 0,
-                                };
-                            } else {
-                                *self = Construct {
-                                    menu: Menu::Move(depth),
-                                    menu_index: index + 1,
-                                }
-                            }
-                        }
-                    }
-                    KeyUp | Character('w') => {
-                        if index == 0 {
-                            if depth == 0 {
-                                let height = world_data.turrets.len() + world_data.obstacles.len();
-                                if height > y {
-                                    *self = Construct {
-                                        menu: Menu::Move(world_data.turrets.len() +
-                                                         world_data.obstacles.len() -
-                                                         y),
-                                        menu_index: y,
-                                    }
-                                } else {
-                                    *self = Construct {
-                                        menu: Menu::Move(0),
-                                        menu_index: height,
-                                    };
-                                }
-                            } else {
-                                *self = Construct {
-                                    menu: Menu::Move(depth - 1),
-                                    menu_index: 0,
-                                };
-                            }
-                        } else {
-                            // index != 0
-                            *self = Construct {
-                                menu: Menu::Move(depth),
-                                menu_index: index - 1,
-                            };
-                        }
-                    }
-                    Character(' ') | Character('\n') => {
-                        if depth + index == world_data.turrets.len() + world_data.obstacles.len() {
-                            *self = Construct {
-                                menu: Menu::Root,
-                                menu_index: 0,
-                            }
-                        } else if depth + index < world_data.turrets.len() {
-                            let item =
-                                world_data.turrets.iter().nth(depth + index).unwrap().clone();
-                            world_data.turrets.remove(&item);
-                            let placement = mem::replace(world_data.statics
-                                                             .get_mut(item.1)
-                                                             .unwrap()
-                                                             .get_mut(item.0)
-                                                             .unwrap(),
-                                                         None)
-                                .unwrap();
-                            *self = Construct {
-                                menu: Menu::Place(placement, item),
-                                menu_index: 0,
-                            };
-                        } else {
-                            let item = world_data.obstacles
-                                .iter()
-                                .nth(depth + index - world_data.turrets.iter().len())
-                                .unwrap()
-                                .clone();
-                            world_data.obstacles.remove(&item);
-                            let placement = mem::replace(world_data.statics
-                                                             .get_mut(item.1)
-                                                             .unwrap()
-                                                             .get_mut(item.0)
-                                                             .unwrap(),
-                                                         None)
-                                .unwrap();
-                            *self = Construct {
-                                menu: Menu::Place(placement, item),
-                                menu_index: 0,
-                            };
-                        }
-                    }
-                    _ => {}
-                }
-            }
             _ => {
-                match i {
-                    KeyDown | Character('s') => {
+                match command {
+                    Move(Dir::S) => {
                         *self = Construct {
-                            menu: menu,
+                            menu,
                             menu_index: (index + 1) % world_data.current_menu_length(&menu),
                         }
                     }
-                    KeyUp | Character('w') => {
+                    Move(Dir::N) => {
                         *self = Construct {
-                            menu: menu,
+                            menu,
                             menu_index: index.checked_sub(1)
                                 .unwrap_or(world_data.current_menu_length(&menu) - 1),
                         }
                     }
-                    Character('q') => *self = End,
-                    KeyBackspace => {
+                    Quit => *self = End,
+                    Back => {
                         *self = Construct {
                             menu: Menu::Root,
                             menu_index: 0,
                         }
                     }
-                    Character(' ') | Character('\n') => {
+                    Select => {
                         match (menu, index) {
-                            (Menu::Root, 0) => {
+                            (Menu::Root, i) => {
+                                match ROOT_ITEMS[i] {
+                                    RootItem::Build => {
+                                        *self = Construct {
+                                            menu: Menu::Build,
+                                            menu_index: 0,
+                                        }
+                                    }
+                                    RootItem::Move => {
+                                        *self = Construct {
+                                            menu: Menu::Move,
+                                            menu_index: 0,
+                                        }
+                                    }
+                                    RootItem::Upgrade => {
+                                        *self = Construct {
+                                            menu: Menu::Upgrade,
+                                            menu_index: 0,
+                                        }
+                                    }
+                                    RootItem::Train => {
+                                        *self = Construct {
+                                            menu: Menu::Train,
+                                            menu_index: 0,
+                                        }
+                                    }
+                                    // Saving needs the whole game, so
+                                    // it's done by 'Game::command'.
+                                    RootItem::Save => {}
+                                    RootItem::Continue => self.start_fight(world_data),
+                                }
+                            }
+                            (Menu::Build, i) if i < BUILDABLES.len() => {
+                                let buildable = BUILDABLES[i];
+                                if !world_data.can_afford(buildable.cost()) {
+                                    return;
+                                }
                                 *self = Construct {
-                                    menu: Menu::Build,
+                                    menu: Menu::Place(buildable.placement(),
+                                                      world_data.goal_location,
+                                                      buildable.cost()),
                                     menu_index: 0,
                                 }
                             }
-                            (Menu::Root, 1) => {
+                            (Menu::Build, _) => {
                                 *self = Construct {
-                                    menu: Menu::Move(0),
+                                    menu: Menu::Root,
                                     menu_index: 0,
                                 }
                             }
-                            (Menu::Root, 2) => {
-                                world_data.wave += 1;
-                                *self = Fight { to_spawn: make_wave(world_data.wave) };
+                            (Menu::Move, i) => {
+                                let nturrets = world_data.turrets.len();
+                                let item = if i < nturrets {
+                                    world_data.turrets.iter().nth(i)
+                                } else {
+                                    world_data.obstacles.iter().nth(i - nturrets)
+                                };
+                                *self = match item.cloned() {
+                                    Some(item) => {
+                                        let placement = world_data.lift(item).unwrap();
+                                        Construct {
+                                            menu: Menu::Place(placement, item, 0),
+                                            menu_index: 0,
+                                        }
+                                    }
+                                    // Back
+                                    None => {
+                                        Construct {
+                                            menu: Menu::Root,
+                                            menu_index: 0,
+                                        }
+                                    }
+                                };
                             }
-                            (Menu::Build, 0) => {
+                            (Menu::Upgrade, i) if i < world_data.turrets.len() => {
+                                let xy = *world_data.turrets.iter().nth(i).unwrap();
                                 *self = Construct {
-                                    menu: Menu::Place(Turret {
-                                                          info: TurretInfo {
-                                                              form: (),
-                                                              cooldown: 0,
-                                                              max_cooldown: 3,
-                                                              range: 50,
-                                                              health: 100,
-                                                              max_health: 100,
-                                                              arrow_speed: 2,
-                                                              damage_factor: 300,
-                                                          },
-                                                      },
-                                                      (X / 2, Y / 2)),
+                                    menu: Menu::UpgradeTurret(xy),
                                     menu_index: 0,
                                 }
                             }
-                            (Menu::Build, 1) => {
+                            (Menu::Upgrade, _) => {
                                 *self = Construct {
-                                    menu: Menu::Place(Obstacle {
-                                                          health: 300,
-                                                          max_health: 300,
-                                                      },
-                                                      (X / 2, Y / 2)),
+                                    menu: Menu::Root,
                                     menu_index: 0,
-                                };
+                                }
+                            }
+                            (Menu::UpgradeTurret(xy), i) if i < TURRET_STATS.len() => {
+                                world_data.upgrade_turret(xy, TURRET_STATS[i]);
+                            }
+                            (Menu::UpgradeTurret(xy), i) if i == TURRET_STATS.len() => {
+                                world_data.aim_turret(xy);
+                            }
+                            (Menu::UpgradeTurret(xy), _) => {
+                                // Go back to the list, with this turret
+                                // still selected.
+                                *self = Construct {
+                                    menu: Menu::Upgrade,
+                                    menu_index: world_data.turrets
+                                        .iter()
+                                        .position(|&t| t == xy)
+                                        .unwrap_or(0),
+                                }
+                            }
+                            (Menu::Train, i) if i < PLAYER_STATS.len() => {
+                                world_data.upgrade_player(PLAYER_STATS[i]);
                             }
-                            (Menu::Build, 2) => {
+                            (Menu::Train, _) => {
                                 *self = Construct {
                                     menu: Menu::Root,
                                     menu_index: 0,
                                 }
                             }
-                            _ => unimplemented!(),
+                            (Menu::Place(..), _) => {
+                                unreachable!("Placing has its own handling above")
+                            }
                         }
                     }
                     _ => (),
@@ -300,41 +281,55 @@ impl GameState {
         };
     }
 
-    fn gameover_handler(&mut self, i: Input) {
-        match i {
-            Character('q') => {
-                *self = End;
-                return;
-            }
-            _ => {}
+    fn start_fight(&mut self, world_data: &mut WorldData) {
+        world_data.wave += 1;
+        *self = Fight {
+            to_spawn: make_wave(world_data.wave, world_data.difficulty, &mut world_data.rng),
+            player_moved: false,
+        };
+    }
+
+    fn gameover_handler(&mut self, command: Command) {
+        if let Quit = command {
+            *self = End;
         }
     }
 
-    fn fight_handler(&mut self, world_data: &mut WorldData, i: Input) {
-        match i {
-            KeyDown | Character('s') => {
-                world_data.move_player(Dir::S);
-            }
-            KeyUp | Character('w') => {
-                world_data.move_player(Dir::N);
-            }
-            KeyLeft | Character('a') => {
-                world_data.move_player(Dir::W);
-            }
-            KeyRight | Character('d') => {
-                world_data.move_player(Dir::E);
-            }
-            Character('q') => {
+    fn fight_handler(&mut self, world_data: &mut WorldData, command: Command) {
+        let dir = match command {
+            Move(dir) => dir,
+            Quit => {
                 *self = End;
                 return;
             }
-            _ => {}
+            _ => return,
         };
 
+        // The player gets at most one step per tick, so holding a key
+        // down doesn't let them outrun everything else.
+        if let Fight { ref mut player_moved, .. } = *self {
+            if !*player_moved {
+                *player_moved = true;
+                world_data.move_player(dir);
+            }
+        }
+    }
+
+    /// Advance the simulation by one tick. This is called on a fixed
+    /// clock, independently of player input, and does nothing outside
+    /// of the fight phase.
+    pub fn tick(&mut self, world_data: &mut WorldData) {
+        match *self {
+            Fight { ref mut player_moved, .. } => *player_moved = false,
+            _ => return,
+        }
+
         // step fiends, turrets, and arrows.
+        let mut paths = mem::take(&mut world_data.paths);
+        paths.update(world_data);
         for fiend_xy in &world_data.fiends.clone() {
             match world_data.mobiles[fiend_xy.1][fiend_xy.0] {
-                Some(Fiend { info }) => world_data.step_fiend(*fiend_xy, info),
+                Some(Fiend { info }) => world_data.step_fiend(*fiend_xy, info, &mut paths),
                 mob => {
                     panic!("({}, {}) is not a fiend (got {:?})!",
                            fiend_xy.0,
@@ -344,6 +339,8 @@ impl GameState {
             }
         }
 
+        world_data.paths = paths;
+
         for turret_xy in &world_data.turrets.clone() {
             match world_data.statics[turret_xy.1][turret_xy.0] {
                 Some(Turret { info }) => world_data.step_turret(*turret_xy, info),
@@ -387,7 +384,7 @@ impl GameState {
 
         for obstacle_xy in &world_data.obstacles.clone() {
             match world_data.statics[obstacle_xy.1][obstacle_xy.0] {
-                Some(Obstacle { health, .. }) if health == 0 => {
+                Some(Obstacle { health: 0, .. }) => {
                     world_data.statics[obstacle_xy.1][obstacle_xy.0] = None;
                     world_data.obstacles.remove(obstacle_xy);
                 }
@@ -402,25 +399,25 @@ impl GameState {
         }
 
         // spawn new fiends
-        match *self {
-            Fight { ref mut to_spawn } => spawn_fiends(world_data, to_spawn),
-            _ => {}
+        if let Fight { ref mut to_spawn, .. } = *self {
+            spawn_fiends(world_data, to_spawn)
         }
 
         // Check for game over
         if world_data.player_info.health == 0 {
             *self = GameOver { msg: "You have died!".to_string() }
         } else {
-            match world_data.statics[world_data.goal_location.1][world_data.goal_location.0] {
-                Some(Goal { health, .. }) if health == 0 => {
-                    *self = GameOver { msg: "The Thing is destroyed!".to_string() }
-                }
-                _ => {}
+            let (goal_x, goal_y) = world_data.goal_location;
+            if let Some(Goal { health: 0, .. }) = world_data.statics[goal_y][goal_x] {
+                *self = GameOver { msg: "The Thing is destroyed!".to_string() }
             }
         }
 
         // Check for phase end
-        if world_data.fiends.is_empty() {
+        if let Fight { ref to_spawn, .. } = *self {
+            if !to_spawn.is_empty() || !world_data.fiends.is_empty() {
+                return;
+            }
             world_data.start_construct();
             *self = Construct {
                 menu: Menu::Root,
@@ -433,17 +430,116 @@ impl GameState {
 impl WorldData {
     fn current_menu_length(&self, menu: &Menu) -> usize {
         match *menu {
-            Menu::Root => 3,
-            Menu::Build => 3,
-            Menu::Move(_) => 1 + self.turrets.len() + self.obstacles.len(),
-            Menu::Place(_, _) => 0,
+            Menu::Root => ROOT_ITEMS.len(),
+            Menu::Build => BUILDABLES.len() + 1,
+            Menu::Move => 1 + self.turrets.len() + self.obstacles.len(),
+            Menu::Upgrade => 1 + self.turrets.len(),
+            Menu::UpgradeTurret(_) => 2 + TURRET_STATS.len(),
+            Menu::Train => 1 + PLAYER_STATS.len(),
+            Menu::Place(..) => 0,
+        }
+    }
+
+    fn can_afford(&mut self, cost: usize) -> bool {
+        if cost > self.cash {
+            self.log_msg(format!("You can't afford that! It costs {}.", cost));
+            false
+        } else {
+            true
+        }
+    }
+
+    // Put a turret or obstacle down and pay for it, if the space is
+    // free and there's enough cash.
+    fn build(&mut self, placement: Static, location: (usize, usize), cost: usize) -> bool {
+        if !self.can_afford(cost) || !self.place(placement, location) {
+            return false;
+        }
+        self.cash -= cost;
+        true
+    }
+
+    // Put a turret or obstacle down, if the space is free.
+    fn place(&mut self, placement: Static, (x, y): (usize, usize)) -> bool {
+        if x == 0 || y == 0 || x >= self.width() - 1 || y >= self.height() - 1 ||
+           self.statics[y][x].is_some() {
+            return false;
+        }
+        self.statics[y][x] = Some(placement);
+        match placement {
+            Turret { .. } => self.turrets.insert((x, y)),
+            Obstacle { .. } => self.obstacles.insert((x, y)),
+            _ => panic!("Placing the unplaceable?"),
+        };
+        true
+    }
+
+    // Pick up a turret or obstacle so it can be placed somewhere else.
+    fn lift(&mut self, (x, y): (usize, usize)) -> Option<Static> {
+        if !self.turrets.remove(&(x, y)) && !self.obstacles.remove(&(x, y)) {
+            return None;
+        }
+        self.statics[y][x].take()
+    }
+
+    fn relocate(&mut self, from: (usize, usize), to: (usize, usize)) -> bool {
+        match self.lift(from) {
+            Some(placement) if self.place(placement, to) => true,
+            Some(placement) => {
+                self.place(placement, from);
+                false
+            }
+            None => false,
         }
     }
 
+    /// Buy the next tier of a player stat, if there's enough cash.
+    pub fn upgrade_player(&mut self, stat: PlayerStat) {
+        let cost = match stat.cost(&self.player_info) {
+            Some(cost) => cost,
+            None => {
+                self.log_msg(format!("{} is already fully upgraded.", stat.name()));
+                return;
+            }
+        };
+        if cost > self.cash {
+            self.log_msg(format!("You can't afford that! It costs {}.", cost));
+            return;
+        }
+
+        let info = &mut self.player_info;
+        match stat {
+            PlayerStat::Health => {
+                info.max_health += 50;
+                info.health = info.max_health;
+            }
+            PlayerStat::Damage => info.damage_factor += 10,
+            PlayerStat::Heal => info.heal_factor += 2,
+            PlayerStat::Armour => info.armour_factor += 25,
+        }
+        info.tiers[stat.index()] += 1;
+        let tier = info.tiers[stat.index()];
+        self.cash -= cost;
+        self.log_msg(format!("Trained {} to tier {}.", stat.name(), tier));
+    }
+
+    /// Take the player out of the room, for games with nobody playing.
+    /// Fiends ignore where they were, and they can't be killed.
+    pub fn remove_player(&mut self) {
+        let (x, y) = self.player_info.location;
+        if self.mobiles[y][x].is_some_and(|p| p.is_player()) {
+            self.mobiles[y][x] = None;
+        }
+        self.player_in_room = false;
+    }
+
     fn move_player(&mut self, dir: Dir) {
+        if !self.player_in_room {
+            return;
+        }
         let old_x = self.player_info.location.0;
         let old_y = self.player_info.location.1;
-        assert!(self.mobiles[old_y][old_x].map_or(false, |p| p.is_player()));
+        assert!(self.mobiles[old_y][old_x].is_some_and(|p| p.is_player()));
         let mut new_x = old_x;
         let mut new_y = old_y;
         match dir {
@@ -454,7 +550,7 @@ impl WorldData {
         };
         match self.statics[new_y][new_x] {
             // Broken turrets can be moved through.
-            Some(Turret { info: TurretInfo { health, .. } }) if health == 0 => {}
+            Some(Turret { info: TurretInfo { health: 0, .. } }) => {}
             Some(mut sta) => {
                 sta.player_interact(&self.player_info);
                 self.statics[new_y][new_x] = Some(sta);
@@ -465,10 +561,10 @@ impl WorldData {
         match self.mobiles[new_y][new_x] {
             Some(Arrow { .. }) => return,
             Some(Fiend { mut info }) => {
-                let damage_factor = self.player_info.damage_factor;
-                info.health = info.health.saturating_sub(damage_factor);
-                self.attack(info, damage_factor);
-                self.mobiles[new_y][new_x] = Some(Fiend { info: info });
+                let damage = resolve_damage(self.player_info.damage_factor, info.armour_factor);
+                info.health = info.health.saturating_sub(damage);
+                self.attack(info, damage);
+                self.mobiles[new_y][new_x] = Some(Fiend { info });
                 return;
             }
             Some(Player) => panic!("Player walked into themself"),
@@ -477,13 +573,14 @@ impl WorldData {
         self.player_info.location = (new_x, new_y);
         self.mobiles[old_y][old_x] = None;
         self.mobiles[new_y][new_x] = Some(Player);
-        assert!(self.mobiles[new_y][new_x].map_or(false, |p| p.is_player()));
+        assert!(self.mobiles[new_y][new_x].is_some_and(|p| p.is_player()));
     }
 
     fn attack(&mut self, info: FiendInfo, damage_factor: usize) {
         if info.health == 0 {
             self.log_msg(format!("{} is hit for {} damage! (dead!)", info.name, damage_factor));
             self.cash += info.value;
+            self.kills += 1;
         } else {
             self.log_msg(format!("{} is hit for {} damage! ({} / {})",
                                  info.name,
@@ -505,7 +602,7 @@ impl WorldData {
             match self.statics[y][x] {
                 Some(Turret { mut info }) => {
                     info.health = info.max_health;
-                    self.statics[y][x] = Some(Turret { info: info });
+                    self.statics[y][x] = Some(Turret { info });
                 }
                 _ => panic!("Not a turret!"),
             }
@@ -515,7 +612,7 @@ impl WorldData {
                 Some(Obstacle { max_health, .. }) => {
                     self.statics[y][x] = Some(Obstacle {
                         health: max_health,
-                        max_health: max_health,
+                        max_health,
                     });
                 }
                 _ => panic!("Not a turret!"),
@@ -530,13 +627,13 @@ fn spawn_fiends(world_data: &mut WorldData, to_spawn: &mut Vec<FiendInfo>) {
     if !to_spawn.is_empty() {
         let mut free_gates = BTreeSet::new();
         for gate_xy in &world_data.gates {
-            if world_data.mobiles[gate_xy.1][gate_xy.0] == None {
+            if world_data.mobiles[gate_xy.1][gate_xy.0].is_none() {
                 free_gates.insert(gate_xy);
             }
         }
 
         while !free_gates.is_empty() && !to_spawn.is_empty() {
-            let mut gate_i = thread_rng().gen_range(0, free_gates.len());
+            let mut gate_i = world_data.rng.gen_range(0, free_gates.len());
             let mut gate = &(0, 0); // hack to remove "possibly uninitialised" errors.
             for g in &free_gates {
                 if gate_i > 0 {
@@ -546,7 +643,7 @@ fn spawn_fiends(world_data: &mut WorldData, to_spawn: &mut Vec<FiendInfo>) {
                 gate = *g;
                 break;
             }
-            let spawn_i = thread_rng().gen_range(0, to_spawn.len());
+            let spawn_i = world_data.rng.gen_range(0, to_spawn.len());
             let fiend = to_spawn[spawn_i];
             world_data.fiends.insert(*gate);
             world_data.mobiles[gate.1][gate.0] = Some(Fiend { info: fiend });
@@ -556,11 +653,8 @@ fn spawn_fiends(world_data: &mut WorldData, to_spawn: &mut Vec<FiendInfo>) {
             // Have to inline this, rather than use
             // 'world_data.log_msg' because it needs a mutable borrow
             // and 'world_data.gates' is borrowed immutably.
-            let len = world_data.log.len();
-            for i in 1..len {
-                world_data.log[len - i] = world_data.log[len - i - 1].clone();
-            }
-            if world_data.wave % 10 == 0 {
+            world_data.log.rotate_right(1);
+            if world_data.difficulty.is_big_boss_wave(world_data.wave) {
                 // Big bosses have proper names.
                 world_data.log[0] = format!("{} appears!", fiend.name);
             } else {
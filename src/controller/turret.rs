@@ -1,3 +1,4 @@
+use fiends::is_boss;
 use model::*;
 use util::*;
 
@@ -17,72 +18,102 @@ impl WorldData {
                 return;
             }
 
-            match find_nearest(&self.fiends, xy) {
-                Some(fiend_xy) if distance(xy, fiend_xy) <= turret_info.range as usize => {
-                    let (fiend_x, fiend_y) = fiend_xy;
-                    let (dx, incx) = make_delta(x, fiend_x);
-                    let (dy, incy) = make_delta(y, fiend_y);
-                    let arrow = Arrow {
-                        info: ArrowInfo {
-                            // Vector
-                            dx: dx,
-                            dy: dy,
-                            dir: (incx, incy),
-                            incx: if dx > dy {
-                                if incx { 1 } else { -1 }
-                            } else {
-                                0
-                            },
-                            incy: if dx > dy {
-                                0
-                            } else {
-                                if incy { 1 } else { -1 }
-                            },
-                            speed: turret_info.arrow_speed,
-                            err: if dx > dy {
-                                dy as i32 * 2 - dx as i32
-                            } else {
-                                dx as i32 * 2 - dy as i32
-                            },
-                            err_inc: if dx > dy {
-                                dy as i32 * 2
-                            } else {
-                                dx as i32 * 2
-                            },
-                            err_dec: if dx > dy {
-                                dx as i32 * 2
-                            } else {
-                                dy as i32 * 2
-                            },
-                            corrx: if dx > dy {
-                                0
-                            } else {
-                                if incx { 1 } else { -1 }
-                            },
-                            corry: if dx > dy {
-                                if incy { 1 } else { -1 }
-                            } else {
-                                0
-                            },
-                            damage_factor: turret_info.damage_factor,
-                        },
-                    };
-                    self.arrows.insert((x, y));
-                    self.mobiles[y][x] = Some(arrow);
-                    new_turret_info.cooldown = turret_info.max_cooldown;
-                }
-                _ => {}
-            };
+            if let Some(fiend_xy) = self.choose_fiend(xy, turret_info) {
+                let mut info = ArrowInfo::new(xy,
+                                              fiend_xy,
+                                              turret_info.arrow_speed,
+                                              turret_info.damage_factor,
+                                              Faction::Defenders);
+                info.splash = turret_info.form.splash();
+                info.slow = turret_info.form.slow();
+                let arrow = Arrow { info };
+                self.arrows.insert((x, y));
+                self.mobiles[y][x] = Some(arrow);
+                new_turret_info.cooldown = turret_info.max_cooldown;
+            }
         }
 
         self.statics[y][x] = Some(Turret { info: new_turret_info });
     }
+
+    // The fiend which the turret most wants to shoot, going by its
+    // targeting mode, out of those in range with nothing in the way.
+    fn choose_fiend(&self, xy: (usize, usize), turret_info: TurretInfo) -> Option<(usize, usize)> {
+        let goal_xy = self.goal_location;
+
+        // The best fiend has the smallest key, and then is the nearest.
+        let key = |fiend_xy: (usize, usize), info: &FiendInfo| {
+            let preference = match turret_info.targeting {
+                Targeting::Nearest => 0,
+                Targeting::Thing => distance(fiend_xy, goal_xy) as isize,
+                Targeting::Strongest => -(info.health as isize),
+                Targeting::Weakest => info.health as isize,
+                Targeting::Richest => -(info.value as isize),
+                Targeting::Boss if is_boss(&info.name) => 0,
+                Targeting::Boss => 1,
+            };
+            (preference, distance(xy, fiend_xy))
+        };
+        let mut in_range = self.fiends
+            .iter()
+            .filter_map(|&fiend_xy| match self.mobiles[fiend_xy.1][fiend_xy.0] {
+                Some(Fiend { ref info }) if distance(xy, fiend_xy) <= turret_info.range => {
+                    Some((key(fiend_xy, info), fiend_xy))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Seeing whether anything's in the way takes a while, so only
+        // the best few fiends are checked.
+        in_range.sort_by_key(|&(key, _)| key);
+        in_range.into_iter()
+            .map(|(_, fiend_xy)| fiend_xy)
+            .find(|&fiend_xy| self.line_of_fire(xy, fiend_xy))
+    }
 }
 
-fn make_delta(start: usize, end: usize) -> (usize, bool) {
-    if start < end {
-        (end - start, true)
-    } else {
-        (start - end, false)
+impl WorldData {
+    /// Buy the next tier of a stat for the turret at this location,
+    /// if there's enough cash.
+    pub fn upgrade_turret(&mut self, (x, y): (usize, usize), stat: TurretStat) {
+        let mut info = match self.statics[y][x] {
+            Some(Turret { info }) => info,
+            _ => return,
+        };
+        let cost = match stat.cost(&info) {
+            Some(cost) => cost,
+            None => {
+                self.log_msg(format!("{} is already fully upgraded.", stat.name()));
+                return;
+            }
+        };
+        if cost > self.cash {
+            self.log_msg(format!("You can't afford that! It costs {}.", cost));
+            return;
+        }
+
+        match stat {
+            TurretStat::Cooldown => info.max_cooldown = info.max_cooldown.saturating_sub(1),
+            TurretStat::Range => info.range += 10,
+            TurretStat::ArrowSpeed => info.arrow_speed += 1,
+            TurretStat::Damage => info.damage_factor += info.damage_factor / 2,
+            TurretStat::Health => {
+                info.max_health += 50;
+                info.health += 50;
+            }
+        }
+        info.tiers[stat.index()] += 1;
+        self.cash -= cost;
+        self.statics[y][x] = Some(Turret { info });
+        self.log_msg(format!("Upgraded {} to tier {}.", stat.name(), info.tiers[stat.index()]));
+    }
+
+    /// Move the turret at this location on to its next targeting mode.
+    pub fn aim_turret(&mut self, (x, y): (usize, usize)) {
+        if let Some(Turret { mut info }) = self.statics[y][x] {
+            info.targeting = info.targeting.next();
+            self.statics[y][x] = Some(Turret { info });
+        }
     }
 }
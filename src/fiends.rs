@@ -1,11 +1,9 @@
 use model::*;
 
-use rand::{Rng, thread_rng};
+use rand::Rng;
 
 use std::cmp::max;
 
-use pancurses::*;
-
 macro_rules! fiend {
     ($prefix:expr, $name:expr) => (FiendName {
         prefix: $prefix,
@@ -24,63 +22,78 @@ const MIN_POINTS: usize = 3;
 
 // Minimum point score, character, and name. Point score is to prevent
 // pitifully weak demons, for instance.
-const SPECIES: [(usize, chtype, &'static str); 20] = [(5, 'r' as chtype, "rat"),
-                                                      (5, 's' as chtype, "slime"),
-                                                      (5, 'w' as chtype, "worm"),
-                                                      (15, 'k' as chtype, "kobold"),
-                                                      (15, 'g' as chtype, "goblin"),
-                                                      (15, 'o' as chtype, "orc"),
-                                                      (75, 'W' as chtype, "werewolf"),
-                                                      (75, 'w' as chtype | A_BOLD, "waynhim"),
-                                                      (75, 'd' as chtype, "demondim"),
-                                                      (100, 'v' as chtype, "vile"),
-                                                      (150, 'G' as chtype, "giant"),
-                                                      (150, 'T' as chtype, "troll"),
-                                                      (200, 'd' as chtype, "demon"),
-                                                      (200, 'a' as chtype, "angel"),
-                                                      (250, 'W' as chtype | A_BOLD, "wight"),
-                                                      (300, 'B' as chtype, "balrog"),
-                                                      (300, 'C' as chtype, "colossus"),
-                                                      (300, 'W' as chtype, "wyrm"),
-                                                      (300, 'D' as chtype, "dragon"),
-                                                      (300, 'V' as chtype, "wyvern")];
-const BIGBOSS_SPECIES: [(usize, chtype, &'static str); 10] =
-    [(30, 'I' as chtype, "Immolator"),
-     (30, 'B' as chtype, "Behemoth"),
-     (80, 'M' as chtype, "Morgoth"),
-     (80, 'K' as chtype, "Kenaustin Ardenol"),
-     (120, 'F' as chtype, "Findail"),
-     (120, 'V' as chtype, "Vain"),
-     (170, 'C' as chtype, "Covenant"),
-     (170, 'F' as chtype, "Foul"),
-     (220, 'I' as chtype, "Infelice"),
-     (220, 'K' as chtype, "Kastenessen")];
+const SPECIES: [(usize, Glyph, &str); 20] = [(5, Glyph::plain('r'), "rat"),
+                                              (5, Glyph::plain('s'), "slime"),
+                                              (5, Glyph::plain('w'), "worm"),
+                                              (15, Glyph::plain('k'), "kobold"),
+                                              (15, Glyph::plain('g'), "goblin"),
+                                              (15, Glyph::plain('o'), "orc"),
+                                              (75, Glyph::plain('W'), "werewolf"),
+                                              (75, Glyph::bold('w'), "waynhim"),
+                                              (75, Glyph::plain('d'), "demondim"),
+                                              (100, Glyph::plain('v'), "vile"),
+                                              (150, Glyph::plain('G'), "giant"),
+                                              (150, Glyph::plain('T'), "troll"),
+                                              (200, Glyph::plain('d'), "demon"),
+                                              (200, Glyph::plain('a'), "angel"),
+                                              (250, Glyph::bold('W'), "wight"),
+                                              (300, Glyph::plain('B'), "balrog"),
+                                              (300, Glyph::plain('C'), "colossus"),
+                                              (300, Glyph::plain('W'), "wyrm"),
+                                              (300, Glyph::plain('D'), "dragon"),
+                                              (300, Glyph::plain('V'), "wyvern")];
+const BIGBOSS_SPECIES: [(usize, Glyph, &str); 10] =
+    [(30, Glyph::plain('I'), "Immolator"),
+     (30, Glyph::plain('B'), "Behemoth"),
+     (80, Glyph::plain('M'), "Morgoth"),
+     (80, Glyph::plain('K'), "Kenaustin Ardenol"),
+     (120, Glyph::plain('F'), "Findail"),
+     (120, Glyph::plain('V'), "Vain"),
+     (170, Glyph::plain('C'), "Covenant"),
+     (170, Glyph::plain('F'), "Foul"),
+     (220, Glyph::plain('I'), "Infelice"),
+     (220, Glyph::plain('K'), "Kastenessen")];
 
 // Variants: scale the minimum point cost of a thing.
-const VARIANTS: [(f64, &'static str); 6] =
+const VARIANTS: [(f64, &str); 6] =
     [(0.5, "lesser "), (1.0, ""), (1.0, ""), (1.0, ""), (2.0, "greater "), (5.0, "ur-")];
-const BOSS_VARIANTS: [(f64, &'static str); 5] =
+const BOSS_VARIANTS: [(f64, &str); 5] =
     [(1.0, "great "), (1.0, "potent "), (1.0, "dark "), (1.0, "mighty "), (1.0, "grim ")];
-const BIGBOSS_VARIANTS: [(f64, &'static str); 5] =
+const BIGBOSS_VARIANTS: [(f64, &str); 5] =
     [(1.0, "ak-Haru "), (1.0, "Lord "), (1.0, "Ur-Lord "), (1.0, "Darth "), (1.0, "na-Mhoram ")];
 
 // Suffixes: make big bosses sound more badass.
-const BIGBOSS_SUFFIXES: [&'static str; 6] =
+const BIGBOSS_SUFFIXES: [&str; 6] =
     [" the Despirer", " the Appointed", " the Guardian", " the Grim", "-cro", "-in"];
 
 // Archetypes: AI roles.
-const ARCHETYPES: [(usize, usize, usize, usize); 3] =
-    [(75, 10, 5, 5), (10, 75, 5, 5), (5, 5, 25, 25)];
+const ARCHETYPES: [(Archetype, usize, usize, usize, usize); 4] =
+    [(Archetype::Hunter, 75, 10, 5, 5),
+     (Archetype::Raider, 10, 75, 5, 5),
+     (Archetype::Wrecker, 5, 5, 25, 25),
+     (Archetype::Archer, 25, 25, 25, 5)];
+
+// Forms: species which don't just walk.
+const FORMS: [(&str, FiendForm); 7] = [("worm", FiendForm::Tunnelling),
+                                       ("wyrm", FiendForm::Tunnelling),
+                                       ("angel", FiendForm::Flying),
+                                       ("dragon", FiendForm::Flying),
+                                       ("wyvern", FiendForm::Flying),
+                                       ("werewolf", FiendForm::Leaping),
+                                       ("kobold", FiendForm::Leaping)];
 
 // Maximum number of types of enemies on each wave.
 const MAX_TYPES: [(usize, usize); 4] = [(2, 2), (5, 3), (15, 5), (30, 10)];
 
-pub fn make_wave(wave: usize) -> Vec<FiendInfo> {
-    let points = 25 + wave * (wave as f64).ln().round() as usize;
+pub fn make_wave<R: Rng>(wave: usize, difficulty: Difficulty, rng: &mut R) -> Vec<FiendInfo> {
+    let points = (25 + wave * (wave as f64).ln().round() as usize) * difficulty.wave_points() / 100;
+    let points = max(MIN_POINTS, points);
+    let weights = difficulty.archetype_weights();
 
-    if wave % 5 == 0 {
+    if difficulty.is_boss_wave(wave) {
         // It's a boss!
-        vec![make_boss(points, wave).expect("Should be able to make a boss")]
+        let big = difficulty.is_big_boss_wave(wave);
+        vec![make_boss(points, big, &weights, rng).expect("Should be able to make a boss")]
     } else {
         // Work out how many types of fiend we'll have.
         let mut max_types = 1;
@@ -90,7 +103,7 @@ pub fn make_wave(wave: usize) -> Vec<FiendInfo> {
             }
             max_types = maxty;
         }
-        let fiend_types = 1 + gen_range_panic(&"fiend_types", 0, max_types);
+        let fiend_types = 1 + gen_range_panic(rng, "fiend_types", 0, max_types);
 
         // Generate fiends.
         let mut fiends: Vec<FiendInfo> = Vec::new();
@@ -101,15 +114,16 @@ pub fn make_wave(wave: usize) -> Vec<FiendInfo> {
             let allocated = if i == fiend_types - 1 {
                 remaining
             } else {
-                gen_range_panic(&"allocated", MIN_POINTS, 1 + max_points)
+                gen_range_panic(rng, "allocated", MIN_POINTS, 1 + max_points)
             };
             remaining -= allocated;
 
             // Determine the cost of one.
-            let cost = gen_range_panic(&"cost", MIN_POINTS, 1 + max(MIN_POINTS, allocated / 3));
+            let cost = gen_range_panic(rng, "cost", MIN_POINTS, 1 + max(MIN_POINTS, allocated / 3));
 
             // Generate fiend.
-            let fiend = make_fiend(cost).expect("Should have been able to afford a fiend");
+            let fiend = make_fiend(cost, &weights, rng)
+                .expect("Should have been able to afford a fiend");
 
             // Populate fiends vector.
             let mut my_remaining = allocated;
@@ -123,86 +137,137 @@ pub fn make_wave(wave: usize) -> Vec<FiendInfo> {
     }
 }
 
-fn make_boss(points: usize, wave: usize) -> Option<FiendInfo> {
-    // Small bosses (every 5th level) are just tough versions of
-    // normal fiends.
+fn make_boss<R: Rng>(points: usize,
+                     big: bool,
+                     weights: &[usize],
+                     rng: &mut R)
+                     -> Option<FiendInfo> {
+    // Small bosses (every 'boss_every' levels for the difficulty) are
+    // just tough versions of normal fiends.
     //
-    // Large bosses (every 10th level) are separate things.
+    // Large bosses (every other boss level) are separate things.
 
-    let boss_archetypes: &[(usize, usize, usize, usize)] =
-        &[(150, 5, 5, 5), (5, 150, 5, 5), (5, 5, 150, 150)];
+    let boss_archetypes: &[(Archetype, usize, usize, usize, usize)] =
+        &[(Archetype::Hunter, 150, 5, 5, 5),
+          (Archetype::Raider, 5, 150, 5, 5),
+          (Archetype::Wrecker, 5, 5, 150, 150),
+          (Archetype::Archer, 150, 150, 150, 5)];
 
-    if wave % 10 == 0 {
+    if big {
         make_fiend_from(points,
                         &BIGBOSS_SPECIES,
                         &BIGBOSS_VARIANTS,
                         &BIGBOSS_SUFFIXES,
-                        boss_archetypes)
+                        boss_archetypes,
+                        weights,
+                        rng)
     } else {
-        make_fiend_from(points, &SPECIES, &BOSS_VARIANTS, &[], boss_archetypes)
+        make_fiend_from(points, &SPECIES, &BOSS_VARIANTS, &[], boss_archetypes, weights, rng)
     }
 }
 
-fn make_fiend(points: usize) -> Option<FiendInfo> {
-    make_fiend_from(points, &SPECIES, &VARIANTS, &[], &ARCHETYPES)
+fn make_fiend<R: Rng>(points: usize, weights: &[usize], rng: &mut R) -> Option<FiendInfo> {
+    make_fiend_from(points, &SPECIES, &VARIANTS, &[], &ARCHETYPES, weights, rng)
 }
 
-fn make_fiend_from(points: usize,
-                   species: &[(usize, chtype, &'static str)],
+// 'weights' says how likely each of the archetypes is to be picked.
+fn make_fiend_from<R: Rng>(points: usize,
+                   species: &[(usize, Glyph, &'static str)],
                    variants: &[(f64, &'static str)],
                    suffixes: &[&'static str],
-                   archetypes: &[(usize, usize, usize, usize)])
+                   archetypes: &[(Archetype, usize, usize, usize, usize)],
+                   weights: &[usize],
+                   rng: &mut R)
                    -> Option<FiendInfo> {
     // Affordable fiends.
-    let mut choices: Vec<(chtype, FiendName)> = Vec::new();
-    for &(min_cost, ch, name) in species.iter() {
+    let mut choices: Vec<(Glyph, FiendName)> = Vec::new();
+    for &(min_cost, glyph, name) in species.iter() {
         for &(scale, title) in variants.iter() {
             if min_cost as f64 * scale > points as f64 {
                 continue;
             }
-            choices.push((ch, fiend!(title, name)));
+            choices.push((glyph, fiend!(title, name)));
             for &descriptor in suffixes.iter() {
-                choices.push((ch, fiend!(title, name, descriptor)));
+                choices.push((glyph, fiend!(title, name, descriptor)));
             }
         }
     }
 
-    if choices.len() == 0 {
+    if choices.is_empty() {
         return None;
     }
 
     // Choose one
-    let (ch, name) = choices[gen_range_panic(&"choose one", 0, choices.len())];
+    let (glyph, name) = choices[gen_range_panic(rng, "choose one", 0, choices.len())];
+
+    let form = FORMS.iter()
+        .find(|f| f.0 == name.name)
+        .map_or(FiendForm::Walking, |f| f.1);
 
     // Assign points to stuff.
     let max_health = points;
-    let damage_factor = gen_range_panic(&"damage_factor", points / 3, 1 + points);
+    let damage_factor = gen_range_panic(rng, "damage_factor", points / 3, 1 + points);
     let armour_factor = points - damage_factor;
 
-    let (player_target_distance,
+    let (archetype,
+         player_target_distance,
          goal_target_distance,
          turret_target_distance,
-         obstacle_target_distance) = archetypes[gen_range_panic(&"archetype", 0, archetypes.len())];
+         obstacle_target_distance) = archetypes[pick_weighted(rng, "archetype", weights)];
 
     Some(FiendInfo {
-        ch: ch,
-        name: name,
-        form: (),
+        glyph,
+        name,
+        form,
+        archetype,
         health: max_health,
-        max_health: max_health,
-        damage_factor: damage_factor,
-        armour_factor: armour_factor,
-        player_target_distance: player_target_distance,
-        goal_target_distance: goal_target_distance,
-        turret_target_distance: turret_target_distance,
-        obstacle_target_distance: obstacle_target_distance,
+        max_health,
+        damage_factor,
+        armour_factor,
+        player_target_distance,
+        goal_target_distance,
+        turret_target_distance,
+        obstacle_target_distance,
         value: points,
+        slowed: 0,
+        reload: 0,
+        stuck: 0,
     })
 }
 
-fn gen_range_panic(msg: &str, lo: usize, hi: usize) -> usize {
+/// Find the static copy of part of a fiend's name, for when names
+/// have been read back in from somewhere.
+pub fn name_part(part: &str) -> Option<&'static str> {
+    let names = SPECIES.iter().chain(BIGBOSS_SPECIES.iter()).map(|s| s.2);
+    let prefixes = VARIANTS.iter()
+        .chain(BOSS_VARIANTS.iter())
+        .chain(BIGBOSS_VARIANTS.iter())
+        .map(|v| v.1);
+    let suffixes = BIGBOSS_SUFFIXES.iter().cloned();
+    names.chain(prefixes).chain(suffixes).find(|&p| p == part)
+}
+
+/// Whether a fiend is a boss, going by its name.
+pub fn is_boss(name: &FiendName) -> bool {
+    let mut boss_prefixes = BOSS_VARIANTS.iter().chain(BIGBOSS_VARIANTS.iter()).map(|v| v.1);
+    boss_prefixes.any(|p| p == name.prefix)
+}
+
+// Pick an index at random, with each one as likely as its weight says.
+fn pick_weighted<R: Rng>(rng: &mut R, msg: &str, weights: &[usize]) -> usize {
+    let mut roll = gen_range_panic(rng, msg, 0, weights.iter().sum());
+    for (i, &weight) in weights.iter().enumerate() {
+        if roll < weight {
+            return i;
+        }
+        roll -= weight;
+    }
+    unreachable!()
+}
+
+fn gen_range_panic<R: Rng>(rng: &mut R, msg: &str, lo: usize, hi: usize) -> usize {
     if lo >= hi {
         panic!("{} > {}: {}", lo, hi, msg);
     }
-    thread_rng().gen_range(lo, hi)
+    rng.gen_range(lo, hi)
 }
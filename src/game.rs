@@ -0,0 +1,194 @@
+use controller::FlowFields;
+use map::Map;
+use model::*;
+use rng::GameRng;
+
+use std::cmp::max;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// How much cash a player starts with, unless they say otherwise.
+pub const DEFAULT_STARTING_CASH: usize = 100;
+
+/// The size of the room, unless the player says otherwise.
+pub const DEFAULT_WIDTH: usize = 63;
+pub const DEFAULT_HEIGHT: usize = 31;
+
+/// The smallest plain room (one without a map) there's space for the
+/// gates in.
+pub const MIN_ROOM_SIZE: usize = 9;
+
+/// Everything which decides how a new game starts out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Settings {
+    /// Everything random about the game is decided by this.
+    pub seed: u64,
+    pub starting_cash: usize,
+    /// The size of the room, which must be at least 'MIN_ROOM_SIZE'
+    /// each way unless there's a map.
+    pub width: usize,
+    pub height: usize,
+    /// The room to play in. Without one, the room is an empty box of
+    /// the size above; with one, the size must match the map.
+    pub map: Option<Map>,
+    pub difficulty: Difficulty,
+}
+
+impl Settings {
+    /// The default settings, with the given seed.
+    pub fn new(seed: u64) -> Settings {
+        Settings {
+            seed,
+            starting_cash: DEFAULT_STARTING_CASH,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            map: None,
+            difficulty: Difficulty::Normal,
+        }
+    }
+
+    /// Play in the given room, which decides the room's size.
+    pub fn set_map(&mut self, map: Map) {
+        self.width = map.width();
+        self.height = map.height();
+        self.map = Some(map);
+    }
+}
+
+/// A game in progress: the world, and what phase of play we're in.
+///
+/// This is everything needed to play, without any notion of how it
+/// gets drawn or where the commands come from.
+pub struct Game {
+    pub world_data: WorldData,
+    pub state: GameState,
+    /// What the game was started with.
+    pub settings: Settings,
+    /// How many ticks have passed since the game started.
+    pub ticks: u64,
+    /// Where the Save menu item saves to.
+    pub save_path: Option<PathBuf>,
+}
+
+impl Game {
+    /// Start a new game. Everything about the game is decided by the
+    /// settings and the player's commands.
+    pub fn new(settings: Settings) -> Game {
+        Game {
+            world_data: initial_world(&settings),
+            state: Startup,
+            settings,
+            ticks: 0,
+            save_path: None,
+        }
+    }
+
+    /// Act on a command from the player.
+    pub fn command(&mut self, command: Command) {
+        if self.is_over() {
+            return;
+        }
+
+        // Picking the difficulty starts the world afresh, which needs
+        // the settings.
+        let difficulty = match (&self.state, command) {
+            (&Startup, Command::Move(Dir::W)) => Some(self.settings.difficulty.easier()),
+            (&Startup, Command::Move(Dir::E)) => Some(self.settings.difficulty.harder()),
+            _ => None,
+        };
+        if let Some(difficulty) = difficulty {
+            self.set_difficulty(difficulty);
+            return;
+        }
+
+        // Saving needs more than the world data, so it can't be done
+        // in the menu code.
+        let save_selected = match self.state {
+            Construct { menu: Menu::Root, menu_index } => ROOT_ITEMS[menu_index] == RootItem::Save,
+            _ => false,
+        };
+        if command == Command::Select && save_selected {
+            let msg = match self.save_path.clone() {
+                Some(path) => {
+                    match self.save_file(&path) {
+                        Ok(()) => format!("Saved to {}.", path.display()),
+                        Err(err) => format!("Couldn't save: {}", err),
+                    }
+                }
+                None => "This game can't be saved.".to_string(),
+            };
+            self.world_data.log_msg(msg);
+            return;
+        }
+
+        self.state.handle(&mut self.world_data, command);
+    }
+
+    /// Change how hard the game is. This can only be done before it
+    /// starts, as it changes how the room is set up.
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        if self.state != Startup {
+            return;
+        }
+        self.settings.difficulty = difficulty;
+        let log = self.world_data.log.clone();
+        self.world_data = initial_world(&self.settings);
+        self.world_data.log = log;
+    }
+
+    /// Advance the world by one tick.
+    pub fn tick(&mut self) {
+        self.state.tick(&mut self.world_data);
+        self.ticks += 1;
+    }
+
+    /// Whether the player has quit.
+    pub fn is_over(&self) -> bool {
+        self.state == End
+    }
+}
+
+pub fn initial_world(settings: &Settings) -> WorldData {
+    let (width, height) = (settings.width, settings.height);
+    let mut map = match settings.map {
+        Some(ref map) => map.clone(),
+        None => {
+            assert!(width >= MIN_ROOM_SIZE && height >= MIN_ROOM_SIZE,
+                    "The room is too small: {}x{}",
+                    width,
+                    height);
+            Map::room(width, height)
+        }
+    };
+    let mut world_data = WorldData {
+        statics: Grid::new(width, height, None),
+        mobiles: Grid::new(width, height, None),
+        fiends: BTreeSet::new(),
+        arrows: BTreeSet::new(),
+        turrets: BTreeSet::new(),
+        obstacles: BTreeSet::new(),
+        gates: BTreeSet::new(),
+        player_info: PlayerInfo {
+            location: (0, 0),
+            health: 100,
+            max_health: 100,
+            damage_factor: 1,
+            heal_factor: 1,
+            armour_factor: 1,
+            tiers: [0; 4],
+        },
+        player_in_room: true,
+        goal_location: (0, 0),
+        log: ["".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string()],
+        cash: settings.starting_cash,
+        wave: 0,
+        kills: 0,
+        difficulty: settings.difficulty,
+        rng: GameRng::new(settings.seed),
+        paths: FlowFields::new(),
+    };
+    map.thing_health = max(1, map.thing_health * settings.difficulty.thing_health() / 100);
+    map.place_into(&mut world_data);
+
+    world_data
+}
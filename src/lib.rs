@@ -0,0 +1,16 @@
+extern crate rand;
+
+mod controller;
+mod game;
+pub mod bot;
+pub mod fiends;
+pub mod map;
+pub mod model;
+pub mod replay;
+pub mod rng;
+pub mod save;
+pub mod scores;
+pub mod util;
+
+pub use controller::FlowFields;
+pub use game::*;
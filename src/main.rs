@@ -1,133 +1,331 @@
-extern crate astar;
+extern crate ludum_dare_37;
 extern crate pancurses;
-extern crate rand;
 
-mod controller;
-mod model;
+mod options;
+mod simulate;
 mod view;
-mod fiends;
-mod util;
+mod waves;
 
-use std::collections::BTreeMap;
-use std::collections::BTreeSet;
-use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant};
 
-use model::*;
+use ludum_dare_37::*;
+use ludum_dare_37::model::*;
+use ludum_dare_37::replay::{Recorder, Replay};
+use ludum_dare_37::scores;
+use ludum_dare_37::scores::{HighScores, Score};
+
+use options::*;
 
 use pancurses::*;
 
-fn initial_world() -> WorldData {
-    let mut world_data = WorldData {
-        statics: [[None; X]; Y],
-        mobiles: [[None; X]; Y],
-        fiends: BTreeSet::new(),
-        arrows: BTreeSet::new(),
-        turrets: BTreeSet::new(),
-        obstacles: BTreeSet::new(),
-        gates: BTreeSet::new(),
-        player_info: PlayerInfo {
-            location: (20, 20),
-            health: 100,
-            max_health: 100,
-            damage_factor: 1,
-            heal_factor: 1,
-            armour_factor: 1,
-        },
-        goal_location: (X / 2, Y / 2),
-        log: ["".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string()],
-        cash: 0,
-        wave: 0,
-    };
-    // add walls!
-    for x in 0..X {
-        world_data.statics[0][x] = Some(Wall);
-        world_data.statics[Y - 1][x] = Some(Wall);
-    }
-    for y in 0..Y {
-        world_data.statics[y][0] = Some(Wall);
-        world_data.statics[y][X - 1] = Some(Wall);
+// How long a single step of the simulation lasts.
+const TICK_MILLIS: u64 = 200;
+
+// How slow and fast replays can go, as multiples of normal speed. Any
+// faster and a tick is over before the screen can be drawn.
+const MIN_REPLAY_SPEED: f64 = 1.0 / 16.0;
+const MAX_REPLAY_SPEED: f64 = 64.0;
+
+// Where games get saved to, unless they were loaded from somewhere
+// else.
+const DEFAULT_SAVE: &str = "thing.sav";
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(1)
+    });
+    match options.mode {
+        Mode::Play => play(options.settings, options.record, options.load),
+        Mode::Waves => {
+            waves::waves(options.settings.seed,
+                         options.settings.difficulty,
+                         options.waves,
+                         options.format)
+        }
+        Mode::Replay(ref path) => replay(path, options.speed),
+        Mode::Bot => bot_games(options.settings, options.games, options.max_wave),
+        Mode::Simulate => simulate::simulate(options.settings, options.max_wave, options.format),
     }
-    // add goal!
-    world_data.statics[Y / 2][X / 2] = Some(Goal {
-        health: 10,
-        max_health: 10,
+}
+
+fn play(settings: Settings, record: Option<String>, load: Option<String>) {
+    let mut game = match load {
+        Some(ref path) => {
+            Game::load_file(path).unwrap_or_else(|err| {
+                eprintln!("Couldn't load {}: {}", path, err);
+                process::exit(1)
+            })
+        }
+        None => {
+            let mut game = Game::new(settings);
+            game.world_data.log_msg(format!("Seed {}", game.settings.seed));
+            game
+        }
+    };
+    game.save_path = Some(PathBuf::from(load.unwrap_or_else(|| DEFAULT_SAVE.to_string())));
+
+    let mut recorder = record.map(|path| {
+        File::create(&path)
+            .and_then(|file| Recorder::new(file, &game.settings))
+            .unwrap_or_else(|err| {
+                eprintln!("Couldn't record to {}: {}", path, err);
+                process::exit(1)
+            })
     });
 
-    // add gates!
-    for x in 0..7 {
-        let gx = x + (X / 2) - 3;
-        world_data.statics[0][gx] = Some(Gate);
-        world_data.statics[Y - 1][gx] = Some(Gate);
-        world_data.gates.insert((gx, 0));
-        world_data.gates.insert((gx, Y - 1));
-    }
+    let (mut high_scores, scores_path) = load_high_scores(&mut game);
+    let mut showing_scores = false;
+    // What to say about the final score, and where it came in the
+    // table, once the game is over.
+    let mut final_score = None;
+
+    let window = start_curses();
+    let mut game_windows = view::setup_render(&window, &game.world_data);
+    let tick_length = Duration::from_millis(TICK_MILLIS);
+    let mut next_tick = Instant::now() + tick_length;
+    while !game.is_over() {
+        game_windows.render(&game.world_data, &game.state);
+        match game.state {
+            Startup if showing_scores => {
+                game_windows.render_scores(&["High Scores".to_string()], &high_scores, None)
+            }
+            GameOver { ref msg } => {
+                if final_score.is_none() {
+                    final_score = Some(record_score(&window,
+                                                    &mut game_windows,
+                                                    &game,
+                                                    msg,
+                                                    &mut high_scores,
+                                                    &scores_path));
+                }
+                if let Some((ref heading, rank)) = final_score {
+                    game_windows.render_scores(heading, &high_scores, rank);
+                }
+            }
+            _ => {}
+        }
+
+        // Wait for input, but no longer than until the next tick is
+        // due. Any keys pressed in the meantime are queued by curses,
+        // so everything gets handled before the world steps.
+        let now = Instant::now();
+        if now < next_tick {
+            window.timeout((next_tick - now).as_millis() as i32);
+            let input = window.getch();
+            if input == Some(Input::KeyResize) {
+                game_windows = view::setup_render(&window, &game.world_data);
+                continue;
+            }
+            // H shows the high scores on the title screen, and then any
+            // key hides them again.
+            if let (Some(key), &Startup) = (input, &game.state) {
+                if showing_scores || key == Input::Character('h') {
+                    showing_scores = !showing_scores;
+                    continue;
+                }
+            }
+            let command = match (input.map(to_command), &game.state) {
+                (Some(Some(command)), _) => command,
+                // Any key gets you past the title screen.
+                (Some(None), &Startup) => Command::Select,
+                _ => continue,
+            };
+            if let Some(ref mut recorder) = recorder {
+                if let Err(err) = recorder.record(game.ticks, command) {
+                    game.world_data.log_msg(format!("Recording failed: {}", err));
+                }
+            }
+            game.command(command);
+            continue;
+        }
 
-    for y in 0..7 {
-        let gy = y + (Y / 2) - 3;
-        world_data.statics[gy][0] = Some(Gate);
-        world_data.statics[gy][X - 1] = Some(Gate);
-        world_data.gates.insert((0, gy));
-        world_data.gates.insert((X - 1, gy));
+        game.tick();
+        next_tick = next_tick_after(next_tick, now, tick_length);
     }
+    let _ = endwin();
+}
 
-    world_data.mobiles[20][20] = Some(Player);
+// Read the high score table, if there's anywhere to keep one. If it
+// can't be read, it's left alone rather than written over.
+fn load_high_scores(game: &mut Game) -> (HighScores, Option<PathBuf>) {
+    let path = match scores::default_path() {
+        Some(path) => path,
+        None => return (HighScores::default(), None),
+    };
+    match HighScores::load_file(&path) {
+        Ok(high_scores) => (high_scores, Some(path)),
+        Err(err) => {
+            game.world_data.log_msg(format!("Couldn't read the high scores: {}", err));
+            (HighScores::default(), None)
+        }
+    }
+}
 
-    return world_data;
+// Put the final score in the high score table if it's good enough,
+// and say how it went.
+fn record_score(window: &Window,
+                game_windows: &mut view::GameWindows,
+                game: &Game,
+                msg: &str,
+                high_scores: &mut HighScores,
+                scores_path: &Option<PathBuf>)
+                -> (Vec<String>, Option<usize>) {
+    let score = Score::of(game);
+    let mut heading = vec![msg.to_string(), view::describe(&score)];
+    let mut rank = None;
+    if high_scores.qualifies(&score) {
+        let name = enter_name(window, game_windows, game, msg, &score);
+        rank = high_scores.add(name, score);
+        if let Some(Err(err)) = scores_path.as_ref().map(|path| high_scores.save_file(path)) {
+            heading.push(format!("Couldn't save the high scores: {}", err));
+        }
+    }
+    heading.push("Press q to quit.".to_string());
+    (heading, rank)
 }
 
-fn main() {
-    if env::args().nth(1) == Some("waves".to_string()) {
-        waves()
-    } else {
-        play()
+// Have the player type in their name for the high score table.
+fn enter_name(window: &Window,
+              game_windows: &mut view::GameWindows,
+              game: &Game,
+              msg: &str,
+              score: &Score)
+              -> String {
+    let mut name = String::new();
+    window.timeout(-1);
+    loop {
+        game_windows.render(&game.world_data, &game.state);
+        game_windows.render_name_entry(msg, score, &name);
+        match window.getch() {
+            Some(Input::Character('\n')) | Some(Input::KeyEnter) => return name,
+            Some(Input::KeyBackspace) |
+            Some(Input::Character('\u{7f}')) |
+            Some(Input::Character('\u{8}')) => {
+                name.pop();
+            }
+            Some(Input::Character(c)) if !c.is_control() &&
+                                         name.chars().count() < scores::MAX_NAME_LENGTH => {
+                name.push(c)
+            }
+            Some(Input::KeyResize) => *game_windows = view::setup_render(window, &game.world_data),
+            _ => {}
+        }
     }
 }
 
-fn waves() {
-    for wave in 1..101 {
-        let the_fiends = fiends::make_wave(wave);
-        let mut the_names = BTreeMap::new();
-        for fiend in the_fiends {
-            let zero = 0;
-            let mut how_many = 0;
-            {
-                how_many = *the_names.get(&fiend.name).unwrap_or(&zero);
+// Have the bot play some games, one after another with consecutive
+// seeds, and say how far it got.
+fn bot_games(settings: Settings, games: usize, max_wave: usize) {
+    let mut waves = Vec::new();
+    for i in 0..games {
+        let mut settings = settings.clone();
+        settings.seed = settings.seed.wrapping_add(i as u64);
+        let seed = settings.seed;
+        let outcome = bot::play(settings, max_wave);
+        match outcome.game_over {
+            Some(msg) => {
+                println!("Game {} (seed {}): lost on wave {}. {}", i + 1, seed, outcome.wave, msg)
             }
-            the_names.insert(fiend.name, how_many + 1);
+            None => println!("Game {} (seed {}): survived {} waves.", i + 1, seed, outcome.wave),
         }
-        let mut names = "".to_string();
-        let mut i = 0;
-        for (name, how_many) in &the_names {
-            i += 1;
-            names = format!("{} {}x {}{}",
-                            names,
-                            how_many,
-                            name,
-                            if i == the_names.len() { "" } else { "," });
+        waves.push(outcome.wave);
+    }
+
+    waves.sort();
+    let mean = waves.iter().sum::<usize>() as f64 / games as f64;
+    println!("Waves reached: lowest {}, median {}, mean {:.1}, highest {}",
+             waves[0],
+             waves[games / 2],
+             mean,
+             waves[games - 1]);
+}
+
+fn replay(path: &str, speed: f64) {
+    let replay = File::open(path)
+        .map_err(From::from)
+        .and_then(|file| Replay::read(BufReader::new(file)))
+        .unwrap_or_else(|err| {
+            eprintln!("Couldn't read replay {}: {}", path, err);
+            process::exit(1)
+        });
+
+    let window = start_curses();
+    let mut game = Game::new(replay.settings);
+    let mut game_windows = view::setup_render(&window, &game.world_data);
+    let mut commands = replay.commands.into_iter().peekable();
+    let mut speed = speed.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED);
+    let mut next_tick = Instant::now();
+    while !game.is_over() {
+        game_windows.render(&game.world_data, &game.state);
+
+        // The keyboard only controls the playback.
+        let now = Instant::now();
+        if now < next_tick {
+            window.timeout((next_tick - now).as_millis() as i32);
+            match window.getch() {
+                Some(Input::Character('q')) => break,
+                Some(Input::Character('+')) => speed = (speed * 2.0).min(MAX_REPLAY_SPEED),
+                Some(Input::Character('-')) => speed = (speed / 2.0).max(MIN_REPLAY_SPEED),
+                Some(Input::KeyResize) => {
+                    game_windows = view::setup_render(&window, &game.world_data);
+                    continue;
+                }
+                _ => continue,
+            }
+            game.world_data.log_msg(format!("Replaying at {}x speed", speed));
+            continue;
         }
-        if wave % 10 == 0 {
-            println!("Wave {}:{} [BIG BOSS]", wave, names);
-        } else if wave % 5 == 0 {
-            println!("Wave {}:{} [BOSS]", wave, names);
-        } else {
-            println!("Wave {}:{}", wave, names);
+
+        // Give the commands from this tick, then step.
+        while let Some(&(tick, command)) = commands.peek() {
+            if tick > game.ticks {
+                break;
+            }
+            game.command(command);
+            commands.next();
         }
+        game.tick();
+        let tick_length = Duration::from_millis(TICK_MILLIS).div_f64(speed);
+        next_tick = next_tick_after(next_tick, now, tick_length);
     }
+    let _ = endwin();
 }
 
-fn play() {
+fn start_curses() -> Window {
     let window = initscr();
     let _ = noecho();
     let _ = curs_set(0);
-    let mut world_data = initial_world();
-    let game_windows = view::setup_render(&window);
     let _ = window.keypad(true);
-    let mut gamestate = model::Startup;
-    while gamestate != model::End {
-        world_data.render(&game_windows, &gamestate);
-        if let Some(i) = window.getch() {
-            gamestate.handle(&mut world_data, i)
-        }
+    window
+}
+
+// When the tick after this one is due.
+fn next_tick_after(this_tick: Instant, now: Instant, tick_length: Duration) -> Instant {
+    let next_tick = this_tick + tick_length;
+    if next_tick < now {
+        // We've fallen behind (eg, the terminal was suspended), so
+        // don't try to catch up with a burst of ticks.
+        now + tick_length
+    } else {
+        next_tick
+    }
+}
+
+fn to_command(i: Input) -> Option<Command> {
+    match i {
+        Input::KeyDown | Input::Character('s') => Some(Command::Move(Dir::S)),
+        Input::KeyUp | Input::Character('w') => Some(Command::Move(Dir::N)),
+        Input::KeyLeft | Input::Character('a') => Some(Command::Move(Dir::W)),
+        Input::KeyRight | Input::Character('d') => Some(Command::Move(Dir::E)),
+        Input::Character(' ') | Input::Character('\n') => Some(Command::Select),
+        Input::KeyBackspace => Some(Command::Back),
+        Input::Character('t') => Some(Command::Aim),
+        Input::Character('q') => Some(Command::Quit),
+        _ => None,
     }
-    let _ = endwin();
 }
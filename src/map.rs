@@ -0,0 +1,343 @@
+//! Rooms described in plain text.
+//!
+//! A map file is a picture of the room, optionally preceded by some
+//! settings, one per line:
+//!
+//! ```text
+//! cash 150
+//! thing 20
+//!
+//! ####---####
+//! #         #
+//! |  O   =  |
+//! #    Y    #
+//! #  @      #
+//! ####---####
+//! ```
+//!
+//! In the picture, `#` is a wall, `-` and `|` are gates (where the
+//! fiends come in), `Y` is the Thing, `@` is where the player starts,
+//! `O` is a turret and `=` is an obstacle. Spaces and `.` are floor.
+//! Other kinds of turret are the first letter of their name: `R` for
+//! rapid, `S` for sniper, `M` for mortar and `F` for frost.
+//! The room must be walled in: everything round its edge is a wall or
+//! a gate. Fiends must be able to walk from at least one gate to the
+//! Thing, breaking through anything in the way but walls.
+//!
+//! The settings are `cash` (how much cash the player starts with) and
+//! `thing` (how much damage the Thing can take). Setting lines start
+//! with a lower-case letter, which no part of the picture does.
+
+use model::*;
+use util::adjacency;
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// How much damage the Thing can take, unless the map says otherwise.
+pub const DEFAULT_THING_HEALTH: usize = 10;
+
+/// The smallest room a map can have: walls all round a single square.
+pub const MIN_MAP_SIZE: usize = 3;
+
+/// A room, ready to start a game in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Map {
+    /// How much cash the player starts with, if the map cares.
+    pub starting_cash: Option<usize>,
+    pub thing_health: usize,
+    // Every row is the same length.
+    rows: Vec<Vec<char>>,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(io::Error),
+    /// Something's wrong at a particular place in the file. The column
+    /// is 0 if it's the whole line.
+    Parse { line: usize, col: usize, msg: String },
+    /// Something's wrong with the map as a whole.
+    Invalid(String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapError::Io(ref err) => write!(f, "{}", err),
+            MapError::Parse { line, col: 0, ref msg } => write!(f, "line {}: {}", line, msg),
+            MapError::Parse { line, col, ref msg } => {
+                write!(f, "line {}, column {}: {}", line, col, msg)
+            }
+            MapError::Invalid(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for MapError {
+    fn from(err: io::Error) -> MapError {
+        MapError::Io(err)
+    }
+}
+
+impl Map {
+    pub fn read<R: BufRead>(input: R) -> Result<Map, MapError> {
+        let mut map = Map {
+            starting_cash: None,
+            thing_health: DEFAULT_THING_HEALTH,
+            rows: Vec::new(),
+        };
+
+        // Remember which line each row came from, for error messages.
+        let mut rows = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            let n = i + 1;
+            if rows.is_empty() && line.trim().is_empty() {
+                continue;
+            }
+            if rows.is_empty() && line.starts_with(|c: char| c.is_ascii_lowercase()) {
+                map.read_setting(&line).map_err(|msg| parse_error(n, 0, msg))?;
+                continue;
+            }
+            rows.push((n, line.trim_end().chars().collect::<Vec<_>>()));
+        }
+        while rows.last().is_some_and(|row| row.1.is_empty()) {
+            rows.pop();
+        }
+
+        let width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+        if width < MIN_MAP_SIZE || rows.len() < MIN_MAP_SIZE {
+            let msg = format!("the room must be at least {}x{}", MIN_MAP_SIZE, MIN_MAP_SIZE);
+            return Err(MapError::Invalid(msg));
+        }
+
+        let (mut thing, mut player, mut gate) = (None, None, false);
+        for &mut (n, ref mut row) in rows.iter_mut() {
+            for (x, &ch) in row.iter().enumerate() {
+                let seen = match ch {
+                    'Y' => &mut thing,
+                    '@' => &mut player,
+                    '-' | '|' => {
+                        gate = true;
+                        continue;
+                    }
+                    '#' | '=' | ' ' | '.' => continue,
+                    _ if turret_form(ch).is_some() => continue,
+                    _ => {
+                        let msg = format!("unknown map character {:?}", ch);
+                        return Err(parse_error(n, x + 1, msg));
+                    }
+                };
+                if let Some((line, col)) = *seen {
+                    let msg = format!("there's already a {:?} at line {}, column {}",
+                                      ch,
+                                      line,
+                                      col);
+                    return Err(parse_error(n, x + 1, msg));
+                }
+                *seen = Some((n, x + 1));
+            }
+            row.resize(width, ' ');
+        }
+        // Nothing can be allowed to walk off the edge of the room.
+        let last = rows.len() - 1;
+        for (y, &(n, ref row)) in rows.iter().enumerate() {
+            for (x, &ch) in row.iter().enumerate() {
+                let edge = y == 0 || y == last || x == 0 || x == width - 1;
+                if edge && !['#', '-', '|'].contains(&ch) {
+                    let msg = format!("the edge of the room must be wall or gate, not {:?}", ch);
+                    return Err(parse_error(n, x + 1, msg));
+                }
+            }
+        }
+        if thing.is_none() {
+            return Err(MapError::Invalid("the map has no Thing (Y)".to_string()));
+        }
+        if player.is_none() {
+            return Err(MapError::Invalid("the map has no player start (@)".to_string()));
+        }
+        if !gate {
+            return Err(MapError::Invalid("the map has no gates (- or |) for fiends to come in \
+                                          through"
+                .to_string()));
+        }
+
+        map.rows = rows.into_iter().map(|row| row.1).collect();
+        if !map.thing_reachable() {
+            return Err(MapError::Invalid("fiends can't get from any gate to the Thing (Y) \
+                                          without going through walls"
+                .to_string()));
+        }
+        Ok(map)
+    }
+
+    // Whether fiends can walk from a gate to the Thing. Anything but a
+    // wall can be broken through, so that's all that gets in the way.
+    fn thing_reachable(&self) -> bool {
+        let size = (self.width(), self.height());
+        let mut seen = Grid::new(size.0, size.1, false);
+        let mut todo = Vec::new();
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, &ch) in row.iter().enumerate() {
+                if ch == '-' || ch == '|' {
+                    seen[y][x] = true;
+                    todo.push((x, y));
+                }
+            }
+        }
+        while let Some((x, y)) = todo.pop() {
+            if self.rows[y][x] == 'Y' {
+                return true;
+            }
+            for (nx, ny) in adjacency((x, y), size) {
+                if !seen[ny][nx] && self.rows[ny][nx] != '#' {
+                    seen[ny][nx] = true;
+                    todo.push((nx, ny));
+                }
+            }
+        }
+        false
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Map, MapError> {
+        Map::read(BufReader::new(File::open(path)?))
+    }
+
+    fn read_setting(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let (name, value) = match (words.next(), words.next(), words.next()) {
+            (Some(name), Some(value), None) => (name, value),
+            _ => return Err(format!("bad setting: {}", line)),
+        };
+        let number = value.parse().map_err(|_| format!("bad value for {}: {}", name, value));
+        match name {
+            "cash" => self.starting_cash = Some(number?),
+            "thing" => {
+                self.thing_health = number?;
+                if self.thing_health == 0 {
+                    return Err("the Thing can't start out destroyed".to_string());
+                }
+            }
+            _ => return Err(format!("unknown setting: {}", name)),
+        }
+        Ok(())
+    }
+
+    /// The plain rectangular room, with a gate in the middle of each
+    /// wall and the Thing in the centre.
+    pub fn room(width: usize, height: usize) -> Map {
+        let mut rows = vec![vec![' '; width]; height];
+        rows[0] = vec!['#'; width];
+        rows[height - 1] = vec!['#'; width];
+        for row in rows.iter_mut() {
+            row[0] = '#';
+            row[width - 1] = '#';
+        }
+        for i in 0..7 {
+            let gx = i + (width / 2) - 3;
+            rows[0][gx] = '-';
+            rows[height - 1][gx] = '-';
+            let gy = i + (height / 2) - 3;
+            rows[gy][0] = '|';
+            rows[gy][width - 1] = '|';
+        }
+        rows[height / 2][width / 2] = 'Y';
+
+        // Not too close to the Thing, unless the room is tiny.
+        let (mut px, py) = (20.min(width - 2), 20.min(height - 2));
+        if (px, py) == (width / 2, height / 2) {
+            px -= 1;
+        }
+        rows[py][px] = '@';
+
+        Map {
+            starting_cash: None,
+            thing_health: DEFAULT_THING_HEALTH,
+            rows,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Fill in an empty world of the right size with what's on the
+    /// map.
+    pub fn place_into(&self, world_data: &mut WorldData) {
+        assert!(world_data.width() == self.width() && world_data.height() == self.height(),
+                "The world is the wrong size for the map");
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, &ch) in row.iter().enumerate() {
+                let sta = match ch {
+                    '#' => Some(Wall),
+                    '-' | '|' => {
+                        world_data.gates.insert((x, y));
+                        Some(Gate)
+                    }
+                    'Y' => {
+                        world_data.goal_location = (x, y);
+                        Some(Goal {
+                            health: self.thing_health,
+                            max_health: self.thing_health,
+                        })
+                    }
+                    _ if turret_form(ch).is_some() => {
+                        world_data.turrets.insert((x, y));
+                        turret_form(ch).map(|form| Buildable::Turret(form).placement())
+                    }
+                    '=' => {
+                        world_data.obstacles.insert((x, y));
+                        Some(Buildable::Obstacle.placement())
+                    }
+                    '@' => {
+                        world_data.player_info.location = (x, y);
+                        world_data.mobiles[y][x] = Some(Player);
+                        None
+                    }
+                    _ => None,
+                };
+                world_data.statics[y][x] = sta;
+            }
+        }
+    }
+}
+
+/// Writes the map out in the same format it's read in.
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cash) = self.starting_cash {
+            writeln!(f, "cash {}", cash)?;
+        }
+        writeln!(f, "thing {}", self.thing_health)?;
+        writeln!(f)?;
+        for row in &self.rows {
+            let row: String = row.iter().collect();
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+// The kind of turret a map character stands for, if it's a turret.
+fn turret_form(ch: char) -> Option<TurretForm> {
+    match ch {
+        'O' => Some(TurretForm::Basic),
+        'R' => Some(TurretForm::Rapid),
+        'S' => Some(TurretForm::Sniper),
+        'M' => Some(TurretForm::Mortar),
+        'F' => Some(TurretForm::Frost),
+        _ => None,
+    }
+}
+
+fn parse_error(line: usize, col: usize, msg: String) -> MapError {
+    MapError::Parse { line, col, msg }
+}
+
@@ -1,9 +1,13 @@
+use std::cmp::min;
 use std::collections::BTreeSet;
 use std::fmt;
-use pancurses::chtype;
+use std::ops::{Index, IndexMut};
+use std::slice::Chunks;
+use std::str::FromStr;
 
-pub const X: usize = 63;
-pub const Y: usize = 31;
+use controller::FlowFields;
+use rng::GameRng;
+use util::ARMOUR_SCALE;
 
 pub use self::Static::*;
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -17,7 +21,9 @@ pub enum Static {
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TurretInfo {
-    pub form: (),
+    pub form: TurretForm,
+    /// How many times each of 'TURRET_STATS' has been upgraded.
+    pub tiers: [u8; 5],
     pub cooldown: usize,
     pub max_cooldown: usize,
     pub range: usize,
@@ -25,6 +31,198 @@ pub struct TurretInfo {
     pub max_health: usize,
     pub arrow_speed: usize,
     pub damage_factor: usize,
+    pub targeting: Targeting,
+    /// How many fiends its arrows have killed.
+    pub kills: usize,
+}
+
+/// The different kinds of turret.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TurretForm {
+    /// A good all-rounder.
+    Basic,
+    /// Fires weak arrows every tick, but not very far.
+    Rapid,
+    /// Fires devastating arrows across the room, but slowly.
+    Sniper,
+    /// Lobs shells which hurt everything around where they land.
+    Mortar,
+    /// Fires arrows which slow down whatever they hit.
+    Frost,
+}
+
+/// Every kind of turret, in the order they appear in the build menu.
+pub const TURRET_FORMS: [TurretForm; 5] = [TurretForm::Basic,
+                                           TurretForm::Rapid,
+                                           TurretForm::Sniper,
+                                           TurretForm::Mortar,
+                                           TurretForm::Frost];
+
+impl TurretForm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TurretForm::Basic => "Turret",
+            TurretForm::Rapid => "Rapid",
+            TurretForm::Sniper => "Sniper",
+            TurretForm::Mortar => "Mortar",
+            TurretForm::Frost => "Frost",
+        }
+    }
+
+    pub fn cost(&self) -> usize {
+        match *self {
+            TurretForm::Basic => 60,
+            TurretForm::Rapid => 80,
+            TurretForm::Sniper => 100,
+            TurretForm::Mortar => 120,
+            TurretForm::Frost => 70,
+        }
+    }
+
+    /// A freshly-built turret of this kind.
+    pub fn info(&self) -> TurretInfo {
+        let (max_cooldown, range, health, arrow_speed, damage_factor) = match *self {
+            TurretForm::Basic => (3, 50, 100, 2, 300),
+            TurretForm::Rapid => (0, 20, 80, 3, 80),
+            TurretForm::Sniper => (8, 100, 60, 6, 900),
+            TurretForm::Mortar => (6, 30, 150, 1, 200),
+            TurretForm::Frost => (4, 40, 100, 2, 50),
+        };
+        TurretInfo {
+            form: *self,
+            tiers: [0; 5],
+            cooldown: 0,
+            max_cooldown,
+            range,
+            health,
+            max_health: health,
+            arrow_speed,
+            damage_factor,
+            targeting: Targeting::Nearest,
+            kills: 0,
+        }
+    }
+
+    /// How far around where they land this kind's arrows do damage.
+    pub fn splash(&self) -> usize {
+        match *self {
+            TurretForm::Mortar => 1,
+            _ => 0,
+        }
+    }
+
+    /// How many ticks this kind's arrows slow fiends down for.
+    pub fn slow(&self) -> usize {
+        match *self {
+            TurretForm::Frost => 10,
+            _ => 0,
+        }
+    }
+}
+
+impl TurretInfo {
+    /// How many upgrades have been bought for this turret.
+    pub fn level(&self) -> usize {
+        self.tiers.iter().map(|&t| t as usize).sum()
+    }
+}
+
+/// Which of the fiends in range a turret shoots at. Ties go to the
+/// nearest fiend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Targeting {
+    Nearest,
+    /// The fiend closest to the Thing.
+    Thing,
+    /// The fiend with the most health left.
+    Strongest,
+    /// The fiend with the least health left.
+    Weakest,
+    /// The fiend worth the most cash.
+    Richest,
+    /// The nearest boss, or the nearest fiend if there are no bosses.
+    Boss,
+}
+
+/// Every targeting mode, in the order a turret cycles through them.
+pub const TARGETINGS: [Targeting; 6] = [Targeting::Nearest,
+                                        Targeting::Thing,
+                                        Targeting::Strongest,
+                                        Targeting::Weakest,
+                                        Targeting::Richest,
+                                        Targeting::Boss];
+
+impl Targeting {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Targeting::Nearest => "Near",
+            Targeting::Thing => "Thing",
+            Targeting::Strongest => "Strong",
+            Targeting::Weakest => "Weak",
+            Targeting::Richest => "Value",
+            Targeting::Boss => "Boss",
+        }
+    }
+
+    /// The mode after this one, going round in a cycle.
+    pub fn next(&self) -> Targeting {
+        let i = TARGETINGS.iter().position(|t| t == self).unwrap();
+        TARGETINGS[(i + 1) % TARGETINGS.len()]
+    }
+}
+
+/// The aspects of a turret which can be upgraded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TurretStat {
+    Cooldown,
+    Range,
+    ArrowSpeed,
+    Damage,
+    Health,
+}
+
+/// Every upgradable stat, in the order they appear in menus and in
+/// 'TurretInfo::tiers'.
+pub const TURRET_STATS: [TurretStat; 5] = [TurretStat::Cooldown,
+                                           TurretStat::Range,
+                                           TurretStat::ArrowSpeed,
+                                           TurretStat::Damage,
+                                           TurretStat::Health];
+
+/// How many times each stat can be upgraded.
+pub const MAX_TIER: u8 = 3;
+
+impl TurretStat {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TurretStat::Cooldown => "Rate",
+            TurretStat::Range => "Range",
+            TurretStat::ArrowSpeed => "Speed",
+            TurretStat::Damage => "Damage",
+            TurretStat::Health => "Armour",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        TURRET_STATS.iter().position(|s| s == self).unwrap()
+    }
+
+    /// The cost of the next upgrade, if there is one.
+    pub fn cost(&self, info: &TurretInfo) -> Option<usize> {
+        let tier = info.tiers[self.index()];
+        // Nothing can fire more than once a tick.
+        if tier >= MAX_TIER || (*self == TurretStat::Cooldown && info.max_cooldown == 0) {
+            return None;
+        }
+        let base = match *self {
+            TurretStat::Cooldown => 50,
+            TurretStat::Range => 20,
+            TurretStat::ArrowSpeed => 20,
+            TurretStat::Damage => 40,
+            TurretStat::Health => 30,
+        };
+        Some(base * (tier as usize + 1))
+    }
 }
 
 pub use self::Mobile::*;
@@ -37,14 +235,11 @@ pub enum Mobile {
 
 impl Mobile {
     pub fn is_player(&self) -> bool {
-        match *self {
-            Player => true,
-            _ => false,
-        }
+        matches!(*self, Player)
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct PlayerInfo {
     pub location: (usize, usize),
     pub health: usize,
@@ -52,6 +247,54 @@ pub struct PlayerInfo {
     pub damage_factor: usize,
     pub heal_factor: usize,
     pub armour_factor: usize,
+    /// How many times each of 'PLAYER_STATS' has been upgraded.
+    pub tiers: [u8; 4],
+}
+
+/// The aspects of the player which can be upgraded between waves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerStat {
+    Health,
+    Damage,
+    Heal,
+    Armour,
+}
+
+/// Every upgradable player stat, in the order they appear in menus
+/// and in 'PlayerInfo::tiers'.
+pub const PLAYER_STATS: [PlayerStat; 4] = [PlayerStat::Health,
+                                           PlayerStat::Damage,
+                                           PlayerStat::Heal,
+                                           PlayerStat::Armour];
+
+impl PlayerStat {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PlayerStat::Health => "Health",
+            PlayerStat::Damage => "Damage",
+            PlayerStat::Heal => "Heal",
+            PlayerStat::Armour => "Armour",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        PLAYER_STATS.iter().position(|s| s == self).unwrap()
+    }
+
+    /// The cost of the next upgrade, if there is one.
+    pub fn cost(&self, info: &PlayerInfo) -> Option<usize> {
+        let tier = info.tiers[self.index()];
+        if tier >= MAX_TIER {
+            return None;
+        }
+        let base = match *self {
+            PlayerStat::Health => 30,
+            PlayerStat::Damage => 40,
+            PlayerStat::Heal => 20,
+            PlayerStat::Armour => 50,
+        };
+        Some(base * (tier as usize + 1))
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -71,11 +314,29 @@ impl fmt::Display for FiendName {
     }
 }
 
+/// A character to draw, and whether it should be drawn in bold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Glyph {
+    pub ch: char,
+    pub bold: bool,
+}
+
+impl Glyph {
+    pub const fn plain(ch: char) -> Glyph {
+        Glyph { ch, bold: false }
+    }
+
+    pub const fn bold(ch: char) -> Glyph {
+        Glyph { ch, bold: true }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct FiendInfo {
-    pub ch: chtype,
+    pub glyph: Glyph,
     pub name: FiendName,
-    pub form: (),
+    pub form: FiendForm,
+    pub archetype: Archetype,
     pub health: usize,
     pub max_health: usize,
     pub damage_factor: usize,
@@ -85,6 +346,86 @@ pub struct FiendInfo {
     pub turret_target_distance: usize,
     pub obstacle_target_distance: usize,
     pub value: usize,
+    /// Slowed fiends only move every other tick, until this runs out.
+    pub slowed: usize,
+    /// Ticks until a ranged fiend can shoot again.
+    pub reload: usize,
+    /// How many turns in a row the fiend has had no way to anything.
+    pub stuck: usize,
+}
+
+/// How a fiend gets around the room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FiendForm {
+    Walking,
+    /// Flies over obstacles and turrets, unless it's attacking them.
+    Flying,
+    /// Burrows through walls.
+    Tunnelling,
+    /// Jumps over a single obstacle or turret in its way.
+    Leaping,
+}
+
+pub const FIEND_FORMS: [FiendForm; 4] = [FiendForm::Walking,
+                                         FiendForm::Flying,
+                                         FiendForm::Tunnelling,
+                                         FiendForm::Leaping];
+
+impl FiendForm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FiendForm::Walking => "walking",
+            FiendForm::Flying => "flying",
+            FiendForm::Tunnelling => "tunnelling",
+            FiendForm::Leaping => "leaping",
+        }
+    }
+}
+
+/// What a fiend is trying to do, which decides what it goes after.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Archetype {
+    /// Goes after the player.
+    Hunter,
+    /// Goes after the Thing.
+    Raider,
+    /// Goes after turrets and obstacles.
+    Wrecker,
+    /// Keeps its distance and shoots at things.
+    Archer,
+}
+
+pub const FIEND_ARCHETYPES: [Archetype; 4] = [Archetype::Hunter,
+                                              Archetype::Raider,
+                                              Archetype::Wrecker,
+                                              Archetype::Archer];
+
+impl Archetype {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Archetype::Hunter => "hunter",
+            Archetype::Raider => "raider",
+            Archetype::Wrecker => "wrecker",
+            Archetype::Archer => "archer",
+        }
+    }
+
+    /// How far away this kind of fiend attacks from, or 0 if it has to
+    /// walk up to things.
+    pub fn range(&self) -> usize {
+        match *self {
+            Archetype::Archer => 6,
+            _ => 0,
+        }
+    }
+}
+
+impl FiendInfo {
+    /// How much raw damage it would take to kill this fiend, after its
+    /// armour soaks some up (ignoring the minimum damage per hit).
+    pub fn effective_health(&self) -> usize {
+        self.health * (ARMOUR_SCALE + self.armour_factor) / ARMOUR_SCALE
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -106,33 +447,311 @@ pub struct ArrowInfo {
     pub corry: i8, // [-1,1]
     // Fiend damage
     pub damage_factor: usize,
+    // Radius of fiends around the impact which are also hit
+    pub splash: usize,
+    // Ticks that hit fiends are slowed for
+    pub slow: usize,
+    // Who fired it, and so who it hurts
+    pub faction: Faction,
+    // Where it was fired from
+    pub origin: (usize, usize),
+}
+
+/// Which side something is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Faction {
+    /// The player and their turrets.
+    Defenders,
+    Fiends,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum Menu {
     Root,
     Build,
-    Move(usize),
-    Place(Static, (usize, usize)),
-}
-
-// pub enum RootItem {
-// Build,
-// Move,
-// Upgrade,
-// Continue
-// }
-//
-// pub enum BuildItem {
-// Turret,
-// Obstacle
-// }
-//
+    /// Choosing a turret or obstacle to move.
+    Move,
+    /// Choosing a turret to upgrade.
+    Upgrade,
+    /// Choosing what to upgrade on the turret at this location.
+    UpgradeTurret((usize, usize)),
+    /// Choosing what to upgrade on the player.
+    Train,
+    /// Something being placed, where it is, and what it will cost to
+    /// put down (nothing, if it's already been built and is just being
+    /// moved).
+    Place(Static, (usize, usize), usize),
+}
+
+/// Things which can be built in the construct phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Buildable {
+    Turret(TurretForm),
+    Obstacle,
+}
+
+/// Everything in the build menu, in order.
+pub const BUILDABLES: [Buildable; 6] = [Buildable::Turret(TurretForm::Basic),
+                                        Buildable::Turret(TurretForm::Rapid),
+                                        Buildable::Turret(TurretForm::Sniper),
+                                        Buildable::Turret(TurretForm::Mortar),
+                                        Buildable::Turret(TurretForm::Frost),
+                                        Buildable::Obstacle];
+
+impl Buildable {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Buildable::Turret(form) => form.name(),
+            Buildable::Obstacle => "Obstacle",
+        }
+    }
+
+    pub fn cost(&self) -> usize {
+        match *self {
+            Buildable::Turret(form) => form.cost(),
+            Buildable::Obstacle => 10,
+        }
+    }
+
+    /// A freshly-built one of these.
+    pub fn placement(&self) -> Static {
+        match *self {
+            Buildable::Turret(form) => Turret { info: form.info() },
+            Buildable::Obstacle => {
+                Obstacle {
+                    health: 300,
+                    max_health: 300,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dir {
+    N,
+    E,
+    S,
+    W,
+}
+
+/// Something the player wants to do. Front-ends translate their
+/// input into these, so the game itself doesn't care where its
+/// instructions come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Move the player in a fight, or the cursor in a menu. On the
+    /// title screen, this picks the difficulty.
+    Move(Dir),
+    /// Pick the highlighted menu item, or put down what's being placed.
+    Select,
+    /// Go back to the top-level menu.
+    Back,
+    /// Build something new at a location, without going through the
+    /// menus. Only works in the construct phase.
+    Build(Buildable, (usize, usize)),
+    /// Move an already-built turret or obstacle to a new location,
+    /// without going through the menus. Only works in the construct
+    /// phase.
+    Place((usize, usize), (usize, usize)),
+    /// Change which fiends the turret being placed shoots at first.
+    Aim,
+    /// Start the next wave. Only works in the construct phase.
+    Continue,
+    Quit,
+}
+
+/// The items in the top-level construct menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootItem {
+    Build,
+    Move,
+    Upgrade,
+    Train,
+    Save,
+    Continue,
+}
+
+/// Everything in the top-level construct menu, in order.
+pub const ROOT_ITEMS: [RootItem; 6] = [RootItem::Build,
+                                       RootItem::Move,
+                                       RootItem::Upgrade,
+                                       RootItem::Train,
+                                       RootItem::Save,
+                                       RootItem::Continue];
+
+impl RootItem {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RootItem::Build => "Build",
+            RootItem::Move => "Move",
+            RootItem::Upgrade => "Upgrade",
+            RootItem::Train => "Train",
+            RootItem::Save => "Save",
+            RootItem::Continue => "Continue",
+        }
+    }
+}
+
+/// How hard the fiends make things.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Nightmare,
+}
+
+/// Every difficulty, easiest first.
+pub const DIFFICULTIES: [Difficulty; 4] =
+    [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Nightmare];
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Nightmare => "Nightmare",
+        }
+    }
+
+    /// How many points each wave has to spend on fiends, as a
+    /// percentage of the normal amount.
+    pub fn wave_points(&self) -> usize {
+        match *self {
+            Difficulty::Easy => 75,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 150,
+            Difficulty::Nightmare => 200,
+        }
+    }
+
+    /// Every how many waves there's a boss. Every other boss is a big
+    /// boss.
+    pub fn boss_every(&self) -> usize {
+        match *self {
+            Difficulty::Easy => 6,
+            Difficulty::Normal => 5,
+            Difficulty::Hard => 4,
+            Difficulty::Nightmare => 3,
+        }
+    }
+
+    /// Whether a wave is a boss on its own.
+    pub fn is_boss_wave(&self, wave: usize) -> bool {
+        wave.is_multiple_of(self.boss_every())
+    }
+
+    /// Whether a wave is a big boss, with a proper name.
+    pub fn is_big_boss_wave(&self, wave: usize) -> bool {
+        wave.is_multiple_of(2 * self.boss_every())
+    }
+
+    /// How likely each archetype is to be picked for a fiend, relative
+    /// to the others, in the order of 'FIEND_ARCHETYPES'. The harder it
+    /// gets, the more fiends go after the defences rather than the
+    /// player.
+    pub fn archetype_weights(&self) -> [usize; 4] {
+        match *self {
+            Difficulty::Easy => [3, 2, 2, 1],
+            Difficulty::Normal => [1, 1, 1, 1],
+            Difficulty::Hard => [1, 1, 2, 2],
+            Difficulty::Nightmare => [1, 2, 3, 3],
+        }
+    }
+
+    /// How much damage the Thing can take, as a percentage of what the
+    /// room says.
+    pub fn thing_health(&self) -> usize {
+        match *self {
+            Difficulty::Easy => 150,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 75,
+            Difficulty::Nightmare => 50,
+        }
+    }
+
+    /// The next difficulty up, or down, stopping at either end.
+    pub fn harder(&self) -> Difficulty {
+        let i = DIFFICULTIES.iter().position(|d| d == self).unwrap();
+        DIFFICULTIES[min(i + 1, DIFFICULTIES.len() - 1)]
+    }
+
+    pub fn easier(&self) -> Difficulty {
+        let i = DIFFICULTIES.iter().position(|d| d == self).unwrap();
+        DIFFICULTIES[i.saturating_sub(1)]
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Difficulty, ()> {
+        DIFFICULTIES.iter().cloned().find(|d| d.name().eq_ignore_ascii_case(s)).ok_or(())
+    }
+}
+
+/// A rectangle of cells, indexed by row and then column, so that
+/// 'grid[y][x]' is the cell at (x, y).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Grid<T> {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Each row in turn, from the top.
+    pub fn rows(&self) -> Chunks<'_, T> {
+        self.cells.chunks(self.width)
+    }
+
+    /// Whether (x, y) is inside the grid.
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        x < self.width && y < self.height
+    }
+}
+
+impl<T> Index<usize> for Grid<T> {
+    type Output = [T];
+
+    fn index(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+}
+
+impl<T> IndexMut<usize> for Grid<T> {
+    fn index_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.cells[y * self.width..(y + 1) * self.width]
+    }
+}
 
 pub struct WorldData {
-    pub statics: [[Option<Static>; X]; Y],
-    pub mobiles: [[Option<Mobile>; X]; Y],
+    pub statics: Grid<Option<Static>>,
+    pub mobiles: Grid<Option<Mobile>>,
     pub player_info: PlayerInfo,
+    /// Whether the player is in the room at all. They're only left out
+    /// when nobody is playing, so fiends have nobody to go after.
+    pub player_in_room: bool,
     pub goal_location: (usize, usize),
     pub fiends: BTreeSet<(usize, usize)>,
     pub turrets: BTreeSet<(usize, usize)>,
@@ -142,14 +761,26 @@ pub struct WorldData {
     pub log: [String; 5],
     pub cash: usize,
     pub wave: usize,
+    /// How many fiends have been killed, by the player or by turrets.
+    pub kills: usize,
+    pub difficulty: Difficulty,
+    pub rng: GameRng,
+    /// How fiends find their way around, kept from tick to tick.
+    pub paths: FlowFields,
 }
 
 impl WorldData {
+    /// The size of the room.
+    pub fn width(&self) -> usize {
+        self.statics.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.statics.height()
+    }
+
     pub fn log_msg(&mut self, msg: String) {
-        let len = self.log.len();
-        for i in 1..len {
-            self.log[len - i] = self.log[len - i - 1].clone();
-        }
+        self.log.rotate_right(1);
         self.log[0] = msg;
     }
 }
@@ -159,7 +790,10 @@ pub use self::GameState::*;
 pub enum GameState {
     Startup,
     Construct { menu: Menu, menu_index: usize },
-    Fight { to_spawn: Vec<FiendInfo> },
+    Fight {
+        to_spawn: Vec<FiendInfo>,
+        player_moved: bool,
+    },
     GameOver { msg: String },
     End,
 }
@@ -0,0 +1,201 @@
+use std::env;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ludum_dare_37::{Settings, MIN_ROOM_SIZE};
+use ludum_dare_37::map::Map;
+use ludum_dare_37::util::parse_size;
+
+pub const USAGE: &str = "usage: ludum_dare_37 [--seed N] [--cash N] [--size WxH] [--record FILE]
+                     [--map FILE] [--load FILE] [--difficulty LEVEL]
+       ludum_dare_37 waves [--seed N] [--waves FIRST-LAST] [--format text|csv|json]
+                           [--difficulty LEVEL]
+       ludum_dare_37 replay FILE [--speed N]
+       ludum_dare_37 bot [--games N] [--max-wave N] [--seed N] [--cash N] [--size WxH]
+                         [--map FILE] [--difficulty LEVEL]
+       ludum_dare_37 simulate FILE [--max-wave N] [--seed N] [--cash N]
+                              [--format text|csv|json] [--difficulty LEVEL]
+
+LEVEL is easy, normal, hard or nightmare.";
+
+pub enum Mode {
+    Play,
+    Waves,
+    Replay(String),
+    /// Let the bot play some games.
+    Bot,
+    /// See how long the layout in a map holds out on its own.
+    Simulate,
+}
+
+/// How the 'waves' and 'simulate' commands print their results.
+#[derive(Clone, Copy)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Format, ()> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct Options {
+    pub mode: Mode,
+    /// How to start a new game.
+    pub settings: Settings,
+    /// Where to record a replay of the game, if anywhere.
+    pub record: Option<String>,
+    /// A saved game to carry on with.
+    pub load: Option<String>,
+    /// How much faster than normal to play back a replay.
+    pub speed: f64,
+    /// Which waves the 'waves' command shows, first and last.
+    pub waves: (usize, usize),
+    pub format: Format,
+    /// How many games the bot plays, and the wave it (or a
+    /// simulation) stops at if it gets that far.
+    pub games: usize,
+    pub max_wave: usize,
+}
+
+pub fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        mode: Mode::Play,
+        settings: Settings::new(random_seed()),
+        record: None,
+        load: None,
+        speed: 1.0,
+        waves: (1, 100),
+        format: Format::Text,
+        games: 10,
+        max_wave: 100,
+    };
+    let (mut seed, mut cash, mut size, mut map, mut difficulty) = (None, None, None, None, None);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "waves" => options.mode = Mode::Waves,
+            "replay" => options.mode = Mode::Replay(value(&mut args, &arg)?),
+            "bot" => options.mode = Mode::Bot,
+            "simulate" => {
+                let path = value::<_, String>(&mut args, &arg)?;
+                map = Some(Map::load_file(&path).map_err(|err| format!("{}: {}", path, err))?);
+                options.mode = Mode::Simulate;
+            }
+            "--seed" => seed = Some(value(&mut args, &arg)?),
+            "--cash" => cash = Some(value(&mut args, &arg)?),
+            "--difficulty" => difficulty = Some(value(&mut args, &arg)?),
+            "--size" => {
+                let value = value::<_, String>(&mut args, &arg)?;
+                size = Some(parse_size(&value)
+                    .ok_or_else(|| format!("bad value for --size: {}", value))?);
+            }
+            "--map" => {
+                let path = value::<_, String>(&mut args, &arg)?;
+                map = Some(Map::load_file(&path).map_err(|err| format!("{}: {}", path, err))?);
+            }
+            "--record" => options.record = Some(value(&mut args, &arg)?),
+            "--load" => options.load = Some(value(&mut args, &arg)?),
+            "--speed" => options.speed = value(&mut args, &arg)?,
+            "--waves" => {
+                let value = value::<_, String>(&mut args, &arg)?;
+                options.waves = parse_range(&value)
+                    .ok_or_else(|| format!("bad value for --waves: {}", value))?;
+            }
+            "--format" => options.format = value(&mut args, &arg)?,
+            "--games" => options.games = value(&mut args, &arg)?,
+            "--max-wave" => options.max_wave = value(&mut args, &arg)?,
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    if options.load.is_some() {
+        if options.record.is_some() {
+            // Replays always start from the beginning of a game.
+            return Err("can't record a loaded game".to_string());
+        }
+        // A loaded game carries on with the settings it was started with.
+        let new_game = [("--seed", seed.is_some()),
+                        ("--cash", cash.is_some()),
+                        ("--size", size.is_some()),
+                        ("--map", map.is_some()),
+                        ("--difficulty", difficulty.is_some())];
+        if let Some(&(flag, _)) = new_game.iter().find(|&&(_, given)| given) {
+            return Err(format!("can't use {} with a loaded game", flag));
+        }
+    }
+    if let Some(seed) = seed {
+        options.settings.seed = seed;
+    }
+    if let Some(map) = map {
+        if size.is_some() {
+            return Err("the map decides the size of the room".to_string());
+        }
+        if let Some(map_cash) = map.starting_cash {
+            options.settings.starting_cash = map_cash;
+        }
+        options.settings.set_map(map);
+    }
+    if let Some((width, height)) = size {
+        // Maps can be smaller, but the plain room needs space for its
+        // gates.
+        if width < MIN_ROOM_SIZE || height < MIN_ROOM_SIZE {
+            return Err(format!("a room without a map must be at least {}x{}",
+                               MIN_ROOM_SIZE,
+                               MIN_ROOM_SIZE));
+        }
+        options.settings.width = width;
+        options.settings.height = height;
+    }
+    if let Some(cash) = cash {
+        options.settings.starting_cash = cash;
+    }
+    if let Some(difficulty) = difficulty {
+        options.settings.difficulty = difficulty;
+    }
+    if options.games == 0 || options.max_wave == 0 {
+        return Err("--games and --max-wave must be positive".to_string());
+    }
+    if !options.speed.is_finite() || options.speed <= 0.0 {
+        return Err("--speed must be a positive number".to_string());
+    }
+    Ok(options)
+}
+
+// Parse a range of waves like "5-10", or a single wave.
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.splitn(2, '-');
+    let first = parts.next()?.parse().ok()?;
+    let last = match parts.next() {
+        Some(last) => last.parse().ok()?,
+        None => first,
+    };
+    if first == 0 || first > last {
+        return None;
+    }
+    Some((first, last))
+}
+
+// Parse the value following a flag.
+fn value<I, T>(args: &mut I, flag: &str) -> Result<T, String>
+    where I: Iterator<Item = String>,
+          T: FromStr
+{
+    let arg = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+    arg.parse().map_err(|_| format!("bad value for {}: {}", flag, arg))
+}
+
+// A seed for when the player doesn't care which game they get.
+fn random_seed() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    now.as_secs() ^ u64::from(now.subsec_nanos())
+}
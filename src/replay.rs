@@ -0,0 +1,269 @@
+//! Recording and playing back games.
+//!
+//! A game is completely determined by its settings and by which
+//! commands were given on which ticks, so that's all a replay holds.
+//! The file format is plain text: a header line, the settings, then
+//! one line per command, prefixed with the tick it was given on. A game
+//! played on a map has the map file inline, after a line saying how
+//! many lines long it is:
+//!
+//! ```text
+//! ludum_dare_37 replay 1
+//! seed 1481993190
+//! cash 100
+//! size 11x6
+//! difficulty normal
+//! map 9
+//! cash 150
+//! thing 20
+//!
+//! ####---####
+//! #         #
+//! |  O   =  |
+//! #    Y    #
+//! #  @      #
+//! ####---####
+//! 3 select
+//! 12 build turret 30 12
+//! 15 continue
+//! 16 move n
+//! ```
+
+use game::{Settings, MIN_ROOM_SIZE};
+use map::{Map, MIN_MAP_SIZE};
+use model::*;
+use util::parse_size;
+
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+const HEADER: &str = "ludum_dare_37 replay 1";
+
+/// A recorded game.
+pub struct Replay {
+    pub settings: Settings,
+    /// Commands, in the order they were given, with the tick they
+    /// were given on.
+    pub commands: Vec<(u64, Command)>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// A line of the file didn't make sense.
+    Parse { line: usize, msg: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Io(ref err) => write!(f, "{}", err),
+            ReplayError::Parse { line, ref msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> ReplayError {
+        ReplayError::Io(err)
+    }
+}
+
+impl Replay {
+    pub fn read<R: BufRead>(input: R) -> Result<Replay, ReplayError> {
+        let mut lines = input.lines();
+
+        if lines.next().unwrap_or_else(|| Ok(String::new()))? != HEADER {
+            return Err(parse_error(1, "not a replay file".to_string()));
+        }
+
+        let mut settings = None;
+        let mut commands = Vec::new();
+        let mut n = 1;
+        let mut size_line = 0;
+        while let Some(line) = lines.next() {
+            let line = line?;
+            n += 1;
+            let mut words = line.trim().splitn(2, ' ');
+            let (first, rest) = match (words.next(), words.next()) {
+                (Some(first), Some(rest)) => (first, rest.trim()),
+                (Some(""), None) => continue,
+                _ => return Err(parse_error(n, format!("bad line: {}", line))),
+            };
+
+            // Settings come before any commands, and the seed always
+            // comes first.
+            match (first.parse(), settings.as_mut()) {
+                (Ok(tick), Some(_)) => {
+                    let command = rest.parse()
+                        .map_err(|_| parse_error(n, format!("bad command: {}", rest)))?;
+                    commands.push((tick, command));
+                }
+                (Err(_), None) if first == "seed" => {
+                    let seed = rest.parse()
+                        .map_err(|_| parse_error(n, format!("bad seed: {}", rest)))?;
+                    settings = Some(Settings::new(seed));
+                }
+                (Err(_), Some(settings)) if commands.is_empty() && first == "map" => {
+                    let count = rest.parse()
+                        .map_err(|_| parse_error(n, format!("bad map length: {}", rest)))?;
+                    let mut text = String::new();
+                    for _ in 0..count {
+                        let line = lines.next()
+                            .ok_or_else(|| parse_error(n, "the map is cut short".to_string()))??;
+                        text.push_str(&line);
+                        text.push('\n');
+                    }
+                    let map = Map::read(text.as_bytes())
+                        .map_err(|err| parse_error(n, format!("bad map: {}", err)))?;
+                    settings.set_map(map);
+                    n += count;
+                }
+                (Err(_), Some(settings)) if commands.is_empty() => {
+                    if first == "size" {
+                        size_line = n;
+                    }
+                    read_setting(settings, first, rest).map_err(|msg| parse_error(n, msg))?;
+                }
+                _ => return Err(parse_error(n, format!("unexpected line: {}", line))),
+            }
+        }
+
+        let settings = settings.ok_or_else(|| parse_error(2, "expected the seed".to_string()))?;
+        // Only a map can make the room smaller than a plain one.
+        if settings.map.is_none() &&
+           (settings.width < MIN_ROOM_SIZE || settings.height < MIN_ROOM_SIZE) {
+            let msg = format!("the room is too small without a map: {}x{}",
+                              settings.width,
+                              settings.height);
+            return Err(parse_error(size_line, msg));
+        }
+        Ok(Replay { settings, commands })
+    }
+}
+
+fn read_setting(settings: &mut Settings, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "cash" => {
+            settings.starting_cash = value.parse()
+                .map_err(|_| format!("bad starting cash: {}", value))?
+        }
+        "size" => {
+            let (width, height) = parse_size(value)
+                .filter(|&(w, h)| w >= MIN_MAP_SIZE && h >= MIN_MAP_SIZE)
+                .ok_or_else(|| format!("bad room size: {}", value))?;
+            settings.width = width;
+            settings.height = height;
+        }
+        "difficulty" => {
+            settings.difficulty = value.parse()
+                .map_err(|_| format!("bad difficulty: {}", value))?
+        }
+        _ => return Err(format!("unknown setting: {}", name)),
+    }
+    Ok(())
+}
+
+fn parse_error(line: usize, msg: String) -> ReplayError {
+    ReplayError::Parse { line, msg }
+}
+
+/// Writes a replay out as the game is played, so that as much as
+/// possible survives if the game crashes.
+pub struct Recorder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W, settings: &Settings) -> io::Result<Recorder<W>> {
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "seed {}", settings.seed)?;
+        writeln!(out, "cash {}", settings.starting_cash)?;
+        writeln!(out, "size {}x{}", settings.width, settings.height)?;
+        writeln!(out, "difficulty {}", settings.difficulty.name().to_lowercase())?;
+        if let Some(ref map) = settings.map {
+            let map = map.to_string();
+            writeln!(out, "map {}", map.lines().count())?;
+            write!(out, "{}", map)?;
+        }
+        out.flush()?;
+        Ok(Recorder { out })
+    }
+
+    pub fn record(&mut self, tick: u64, command: Command) -> io::Result<()> {
+        writeln!(self.out, "{} {}", tick, command)?;
+        self.out.flush()
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Command::Move(dir) => write!(f, "move {}", dir),
+            Command::Select => write!(f, "select"),
+            Command::Back => write!(f, "back"),
+            Command::Build(buildable, (x, y)) => {
+                write!(f, "build {} {} {}", buildable.name().to_lowercase(), x, y)
+            }
+            Command::Place((from_x, from_y), (to_x, to_y)) => {
+                write!(f, "place {} {} {} {}", from_x, from_y, to_x, to_y)
+            }
+            Command::Aim => write!(f, "aim"),
+            Command::Continue => write!(f, "continue"),
+            Command::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Command, ()> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let num = |i: usize| words[i].parse::<usize>().map_err(|_| ());
+        match words.as_slice() {
+            ["move", dir] => Ok(Command::Move(dir.parse()?)),
+            ["select"] => Ok(Command::Select),
+            ["back"] => Ok(Command::Back),
+            ["build", name, _, _] => {
+                let buildable = BUILDABLES.iter()
+                    .find(|b| b.name().eq_ignore_ascii_case(name))
+                    .ok_or(())?;
+                Ok(Command::Build(*buildable, (num(2)?, num(3)?)))
+            }
+            ["place", _, _, _, _] => Ok(Command::Place((num(1)?, num(2)?), (num(3)?, num(4)?))),
+            ["aim"] => Ok(Command::Aim),
+            ["continue"] => Ok(Command::Continue),
+            ["quit"] => Ok(Command::Quit),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Dir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Dir::N => "n",
+            Dir::E => "e",
+            Dir::S => "s",
+            Dir::W => "w",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Dir {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Dir, ()> {
+        match s {
+            "n" => Ok(Dir::N),
+            "e" => Ok(Dir::E),
+            "s" => Ok(Dir::S),
+            "w" => Ok(Dir::W),
+            _ => Err(()),
+        }
+    }
+}
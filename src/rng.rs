@@ -0,0 +1,54 @@
+use rand::Rng;
+
+/// The random number generator behind everything random in a game:
+/// wave generation and fiend spawning both draw from this, so a game
+/// is entirely determined by its seed and the player's commands.
+///
+/// This is xorshift128, the same algorithm as `rand::XorShiftRng`,
+/// but we own the state so that it can be inspected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameRng {
+    state: [u32; 4],
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        // Spread the seed over the whole state with splitmix64, so
+        // that similar seeds don't give similar games.
+        let mut z = seed;
+        let mut state = [0; 4];
+        for word in state.iter_mut() {
+            z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut x = z;
+            x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *word = (x ^ (x >> 31)) as u32;
+        }
+        // xorshift gets stuck on an all-zero state.
+        if state == [0; 4] {
+            state[0] = 1;
+        }
+        GameRng { state }
+    }
+
+    /// Pick up where another generator left off.
+    pub fn from_state(state: [u32; 4]) -> GameRng {
+        GameRng { state }
+    }
+
+    pub fn state(&self) -> [u32; 4] {
+        self.state
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        let t = self.state[0] ^ (self.state[0] << 11);
+        self.state[0] = self.state[1];
+        self.state[1] = self.state[2];
+        self.state[2] = self.state[3];
+        let w = self.state[3];
+        self.state[3] = w ^ (w >> 19) ^ (t ^ (t >> 8));
+        self.state[3]
+    }
+}
@@ -0,0 +1,591 @@
+//! Saving and loading games.
+//!
+//! Saves are plain text. The first line gives the format version,
+//! and every line after that is one record: a kind, followed by
+//! `key=value` fields. For example:
+//!
+//! ```text
+//! ludum_dare_37 save 1
+//! game seed=1481993190 starting_cash=100 width=63 height=31 difficulty=hard ticks=1234 ...
+//! world wave=3 cash=72 kills=41
+//! player x=20 y=20 health=100 max_health=100 damage=1 heal=1 armour=1 tiers=0,0,0,0
+//! turret x=30 y=12 cooldown=0 max_cooldown=3 range=50 health=100 ...
+//! state kind=construct
+//! ```
+//!
+//! Fields which are missing take a default value where there's a
+//! sensible one, so that saves from older versions of the game still
+//! load when new fields are added. The version only needs changing if
+//! the meaning of existing records changes.
+
+use fiends;
+use game::*;
+use map::MIN_MAP_SIZE;
+use model::*;
+use rng::GameRng;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+const HEADER: &str = "ludum_dare_37 save";
+const VERSION: usize = 1;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    /// The save is from a version of the game we don't understand.
+    Version(String),
+    /// A line of the save didn't make sense.
+    Parse { line: usize, msg: String },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SaveError::Io(ref err) => write!(f, "{}", err),
+            SaveError::Version(ref version) => write!(f, "unsupported save version: {}", version),
+            SaveError::Parse { line, ref msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> SaveError {
+        SaveError::Io(err)
+    }
+}
+
+impl Game {
+    pub fn save<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let world_data = &self.world_data;
+        let rng = world_data.rng.state();
+        let player = &world_data.player_info;
+
+        writeln!(out, "{} {}", HEADER, VERSION)?;
+        writeln!(out,
+                 "game seed={} starting_cash={} width={} height={} difficulty={} ticks={} \
+                  rng={},{},{},{}",
+                 self.settings.seed,
+                 self.settings.starting_cash,
+                 self.settings.width,
+                 self.settings.height,
+                 self.settings.difficulty.name().to_lowercase(),
+                 self.ticks,
+                 rng[0],
+                 rng[1],
+                 rng[2],
+                 rng[3])?;
+        writeln!(out,
+                 "world wave={} cash={} kills={}",
+                 world_data.wave,
+                 world_data.cash,
+                 world_data.kills)?;
+        writeln!(out,
+                 "player x={} y={} health={} max_health={} damage={} heal={} armour={} \
+                  tiers={}",
+                 player.location.0,
+                 player.location.1,
+                 player.health,
+                 player.max_health,
+                 player.damage_factor,
+                 player.heal_factor,
+                 player.armour_factor,
+                 join_tiers(&player.tiers))?;
+        writeln!(out,
+                 "goal x={} y={}",
+                 world_data.goal_location.0,
+                 world_data.goal_location.1)?;
+        // Oldest first, so they end up in the right order on loading.
+        for msg in world_data.log.iter().rev() {
+            writeln!(out, "log {}", msg)?;
+        }
+
+        for (y, row) in world_data.statics.rows().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                match *cell {
+                    Some(Wall) => writeln!(out, "wall x={} y={}", x, y)?,
+                    Some(Gate) => writeln!(out, "gate x={} y={}", x, y)?,
+                    Some(Goal { health, max_health }) => {
+                        writeln!(out,
+                                 "thing x={} y={} health={} max_health={}",
+                                 x,
+                                 y,
+                                 health,
+                                 max_health)?
+                    }
+                    Some(Turret { info }) => {
+                        writeln!(out, "turret x={} y={} {}", x, y, turret_fields(&info))?
+                    }
+                    Some(Obstacle { health, max_health }) => {
+                        writeln!(out,
+                                 "obstacle x={} y={} health={} max_health={}",
+                                 x,
+                                 y,
+                                 health,
+                                 max_health)?
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        for (y, row) in world_data.mobiles.rows().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                match *cell {
+                    // The player's location is saved with the rest of
+                    // their info.
+                    Some(Player) | None => {}
+                    Some(Fiend { info }) => {
+                        writeln!(out, "fiend x={} y={} {}", x, y, fiend_fields(&info))?
+                    }
+                    Some(Arrow { info }) => {
+                        writeln!(out, "arrow x={} y={} {}", x, y, arrow_fields(&info))?
+                    }
+                }
+            }
+        }
+
+        match self.state {
+            Startup => writeln!(out, "state kind=startup")?,
+            Construct { .. } => writeln!(out, "state kind=construct")?,
+            Fight { ref to_spawn, player_moved } => {
+                writeln!(out, "state kind=fight player_moved={}", player_moved)?;
+                for info in to_spawn {
+                    writeln!(out, "spawn {}", fiend_fields(info))?;
+                }
+            }
+            GameOver { ref msg } => writeln!(out, "state kind=gameover msg={}", escape(msg))?,
+            End => writeln!(out, "state kind=end")?,
+        }
+
+        out.flush()
+    }
+
+    pub fn load<R: BufRead>(input: R) -> Result<Game, SaveError> {
+        let mut lines = input.lines();
+
+        let header = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+        if !header.starts_with(HEADER) {
+            return Err(SaveError::Parse {
+                line: 1,
+                msg: "not a save file".to_string(),
+            });
+        }
+        let version = header[HEADER.len()..].trim();
+        if version.parse() != Ok(VERSION) {
+            return Err(SaveError::Version(version.to_string()));
+        }
+
+        // Start from an empty room, and fill it in.
+        let mut game = Game::new(Settings::new(0));
+        {
+            let world_data = &mut game.world_data;
+            world_data.statics = Grid::new(DEFAULT_WIDTH, DEFAULT_HEIGHT, None);
+            world_data.mobiles = Grid::new(DEFAULT_WIDTH, DEFAULT_HEIGHT, None);
+            world_data.gates.clear();
+            world_data.log = Default::default();
+        }
+        let mut state = None;
+
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let n = i + 2;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with("log ") || line == "log" {
+                game.world_data.log_msg(line.get(4..).unwrap_or("").to_string());
+                continue;
+            }
+            let record = Record::parse(&line).map_err(|msg| SaveError::Parse { line: n, msg })?;
+            record.load_into(&mut game, &mut state)
+                .map_err(|msg| SaveError::Parse { line: n, msg })?;
+        }
+
+        game.state = state.ok_or(SaveError::Parse {
+                line: 0,
+                msg: "no game state".to_string(),
+            })?;
+        let (x, y) = game.world_data.player_info.location;
+        game.world_data.mobiles[y][x] = Some(Player);
+
+        Ok(game)
+    }
+
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.save(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Game, SaveError> {
+        Game::load(BufReader::new(File::open(path)?))
+    }
+}
+
+fn turret_fields(info: &TurretInfo) -> String {
+    format!("form={} cooldown={} max_cooldown={} range={} health={} max_health={} \
+             arrow_speed={} damage={} tiers={} aim={} kills={}",
+            info.form.name().to_lowercase(),
+            info.cooldown,
+            info.max_cooldown,
+            info.range,
+            info.health,
+            info.max_health,
+            info.arrow_speed,
+            info.damage_factor,
+            join_tiers(&info.tiers),
+            info.targeting.name().to_lowercase(),
+            info.kills)
+}
+
+fn join_tiers(tiers: &[u8]) -> String {
+    tiers.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn fiend_fields(info: &FiendInfo) -> String {
+    let suffix = match info.name.suffix {
+        Some(suffix) => format!(" suffix={}", escape(suffix)),
+        None => String::new(),
+    };
+    format!("glyph={} bold={} prefix={} name={}{} form={} archetype={} health={} \
+             max_health={} damage={} armour={} player_distance={} goal_distance={} \
+             turret_distance={} obstacle_distance={} value={} slowed={} reload={} stuck={}",
+            info.glyph.ch as u32,
+            info.glyph.bold,
+            escape(info.name.prefix),
+            escape(info.name.name),
+            suffix,
+            info.form.name(),
+            info.archetype.name(),
+            info.health,
+            info.max_health,
+            info.damage_factor,
+            info.armour_factor,
+            info.player_target_distance,
+            info.goal_target_distance,
+            info.turret_target_distance,
+            info.obstacle_target_distance,
+            info.value,
+            info.slowed,
+            info.reload,
+            info.stuck)
+}
+
+fn arrow_fields(info: &ArrowInfo) -> String {
+    format!("dx={} dy={} dir_x={} dir_y={} incx={} incy={} speed={} err={} err_inc={} \
+             err_dec={} corrx={} corry={} damage={} splash={} slow={} fiends={} origin_x={} \
+             origin_y={}",
+            info.dx,
+            info.dy,
+            info.dir.0,
+            info.dir.1,
+            info.incx,
+            info.incy,
+            info.speed,
+            info.err,
+            info.err_inc,
+            info.err_dec,
+            info.corrx,
+            info.corry,
+            info.damage_factor,
+            info.splash,
+            info.slow,
+            info.faction == Faction::Fiends,
+            info.origin.0,
+            info.origin.1)
+}
+
+// Values can't contain spaces, and names never contain underscores.
+fn escape(s: &str) -> String {
+    s.replace(' ', "_")
+}
+
+fn unescape(s: &str) -> String {
+    s.replace('_', " ")
+}
+
+/// One line of a save file.
+struct Record<'a> {
+    kind: &'a str,
+    fields: BTreeMap<&'a str, &'a str>,
+}
+
+impl<'a> Record<'a> {
+    fn parse(line: &'a str) -> Result<Record<'a>, String> {
+        let mut words = line.split_whitespace();
+        let kind = words.next().unwrap_or("");
+        let mut fields = BTreeMap::new();
+        for word in words {
+            match word.find('=') {
+                Some(i) => fields.insert(&word[..i], &word[i + 1..]),
+                None => return Err(format!("expected 'key=value', got '{}'", word)),
+            };
+        }
+        Ok(Record { kind, fields })
+    }
+
+    fn get<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        match self.fields.get(key) {
+            Some(value) => value.parse().map_err(|_| format!("bad value for {}: {}", key, value)),
+            None => Err(format!("missing {}", key)),
+        }
+    }
+
+    fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        if self.fields.contains_key(key) {
+            self.get(key)
+        } else {
+            Ok(default)
+        }
+    }
+
+    fn xy(&self, world_data: &WorldData) -> Result<(usize, usize), String> {
+        let (x, y) = (self.get("x")?, self.get("y")?);
+        if world_data.statics.contains((x, y)) {
+            Ok((x, y))
+        } else {
+            Err(format!("({}, {}) is outside the room", x, y))
+        }
+    }
+
+    fn name_part(&self, key: &str) -> Result<Option<&'static str>, String> {
+        match self.fields.get(key) {
+            Some(part) => {
+                fiends::name_part(&unescape(part))
+                    .map(Some)
+                    .ok_or_else(|| format!("unknown name: {}", part))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn load_into(&self, game: &mut Game, state: &mut Option<GameState>) -> Result<(), String> {
+        let world_data = &mut game.world_data;
+        match self.kind {
+            "game" => {
+                game.settings.seed = self.get("seed")?;
+                game.settings.starting_cash = self.get_or("starting_cash", DEFAULT_STARTING_CASH)?;
+                // Rooms were all the same size before they could vary.
+                let width = self.get_or("width", DEFAULT_WIDTH)?;
+                let height = self.get_or("height", DEFAULT_HEIGHT)?;
+                // The room might have come from a map, so it can be
+                // smaller than a plain one.
+                if width < MIN_MAP_SIZE || height < MIN_MAP_SIZE {
+                    return Err(format!("the room is too small: {}x{}", width, height));
+                }
+                game.settings.width = width;
+                game.settings.height = height;
+                game.settings.difficulty = self.get_or("difficulty", Difficulty::Normal)?;
+                world_data.difficulty = game.settings.difficulty;
+                world_data.statics = Grid::new(width, height, None);
+                world_data.mobiles = Grid::new(width, height, None);
+                game.ticks = self.get("ticks")?;
+                let rng: Vec<u32> = self.get::<String>("rng")?
+                    .split(',')
+                    .map(|word| word.parse().map_err(|_| format!("bad rng state: {}", word)))
+                    .collect::<Result<_, _>>()?;
+                if rng.len() != 4 {
+                    return Err("rng state should have four parts".to_string());
+                }
+                world_data.rng = GameRng::from_state([rng[0], rng[1], rng[2], rng[3]]);
+            }
+            "world" => {
+                world_data.wave = self.get("wave")?;
+                world_data.cash = self.get("cash")?;
+                world_data.kills = self.get_or("kills", 0)?;
+            }
+            "player" => {
+                let location = self.xy(world_data)?;
+                let info = &mut world_data.player_info;
+                info.location = location;
+                info.health = self.get("health")?;
+                info.max_health = self.get("max_health")?;
+                info.damage_factor = self.get_or("damage", 1)?;
+                info.heal_factor = self.get_or("heal", 1)?;
+                info.armour_factor = self.get_or("armour", 1)?;
+                self.tiers(&mut info.tiers)?;
+            }
+            "goal" => world_data.goal_location = self.xy(world_data)?,
+            "wall" => {
+                let (x, y) = self.xy(world_data)?;
+                world_data.statics[y][x] = Some(Wall);
+            }
+            "gate" => {
+                let (x, y) = self.xy(world_data)?;
+                world_data.statics[y][x] = Some(Gate);
+                world_data.gates.insert((x, y));
+            }
+            "thing" => {
+                let (x, y) = self.xy(world_data)?;
+                world_data.statics[y][x] = Some(Goal {
+                    health: self.get("health")?,
+                    max_health: self.get("max_health")?,
+                });
+            }
+            "turret" => {
+                let (x, y) = self.xy(world_data)?;
+                world_data.statics[y][x] = Some(Turret { info: self.turret()? });
+                world_data.turrets.insert((x, y));
+            }
+            "obstacle" => {
+                let (x, y) = self.xy(world_data)?;
+                world_data.statics[y][x] = Some(Obstacle {
+                    health: self.get("health")?,
+                    max_health: self.get("max_health")?,
+                });
+                world_data.obstacles.insert((x, y));
+            }
+            "fiend" => {
+                let (x, y) = self.xy(world_data)?;
+                world_data.mobiles[y][x] = Some(Fiend { info: self.fiend()? });
+                world_data.fiends.insert((x, y));
+            }
+            "arrow" => {
+                let (x, y) = self.xy(world_data)?;
+                world_data.mobiles[y][x] = Some(Arrow { info: self.arrow((x, y))? });
+                world_data.arrows.insert((x, y));
+            }
+            "state" => {
+                *state = Some(match self.get::<String>("kind")?.as_str() {
+                    "startup" => Startup,
+                    "construct" => {
+                        Construct {
+                            menu: Menu::Root,
+                            menu_index: 0,
+                        }
+                    }
+                    "fight" => {
+                        Fight {
+                            to_spawn: Vec::new(),
+                            player_moved: self.get_or("player_moved", false)?,
+                        }
+                    }
+                    "gameover" => GameOver { msg: unescape(&self.get::<String>("msg")?) },
+                    "end" => End,
+                    kind => return Err(format!("unknown state: {}", kind)),
+                })
+            }
+            "spawn" => {
+                match *state {
+                    Some(Fight { ref mut to_spawn, .. }) => to_spawn.push(self.fiend()?),
+                    _ => return Err("fiends to spawn outside of a fight".to_string()),
+                }
+            }
+            kind => return Err(format!("unknown record: {}", kind)),
+        }
+        Ok(())
+    }
+
+    /// Upgrade tiers, which are all zero in saves from before upgrades
+    /// existed.
+    fn tiers(&self, tiers: &mut [u8]) -> Result<(), String> {
+        let words = self.get_or("tiers", String::new())?;
+        for (i, word) in words.split(',').filter(|w| !w.is_empty()).enumerate() {
+            let tier = word.parse().map_err(|_| format!("bad tier: {}", word))?;
+            match tiers.get_mut(i) {
+                Some(t) if tier <= MAX_TIER => *t = tier,
+                _ => return Err(format!("bad tiers: {}", words)),
+            }
+        }
+        Ok(())
+    }
+
+    fn turret(&self) -> Result<TurretInfo, String> {
+        let mut tiers = [0; 5];
+        self.tiers(&mut tiers)?;
+        // Before there were different kinds of turret, there was just
+        // the basic one.
+        let form = self.get_or("form", "turret".to_string())?;
+        let form = TURRET_FORMS.iter()
+            .find(|f| f.name().eq_ignore_ascii_case(&form))
+            .ok_or_else(|| format!("unknown turret: {}", form))?;
+        let aim = self.get_or("aim", "near".to_string())?;
+        let targeting = TARGETINGS.iter()
+            .find(|t| t.name().eq_ignore_ascii_case(&aim))
+            .ok_or_else(|| format!("unknown targeting: {}", aim))?;
+        Ok(TurretInfo {
+            form: *form,
+            tiers,
+            cooldown: self.get("cooldown")?,
+            max_cooldown: self.get("max_cooldown")?,
+            range: self.get("range")?,
+            health: self.get("health")?,
+            max_health: self.get("max_health")?,
+            arrow_speed: self.get("arrow_speed")?,
+            damage_factor: self.get("damage")?,
+            targeting: *targeting,
+            kills: self.get_or("kills", 0)?,
+        })
+    }
+
+    fn fiend(&self) -> Result<FiendInfo, String> {
+        let ch = self.get::<u32>("glyph")?;
+        let ch = ::std::char::from_u32(ch).ok_or_else(|| format!("bad glyph: {}", ch))?;
+        let form = self.get_or("form", "walking".to_string())?;
+        let form = FIEND_FORMS.iter()
+            .find(|f| f.name() == form)
+            .ok_or_else(|| format!("unknown fiend form: {}", form))?;
+        // Fiends from before archetypes were recorded were all melee
+        // fighters, and the archetype is only used for range.
+        let archetype = self.get_or("archetype", "raider".to_string())?;
+        let archetype = FIEND_ARCHETYPES.iter()
+            .find(|a| a.name() == archetype)
+            .ok_or_else(|| format!("unknown archetype: {}", archetype))?;
+        Ok(FiendInfo {
+            glyph: Glyph {
+                ch,
+                bold: self.get_or("bold", false)?,
+            },
+            name: FiendName {
+                prefix: self.name_part("prefix")?.unwrap_or(""),
+                name: self.name_part("name")?.ok_or("missing name")?,
+                suffix: self.name_part("suffix")?,
+            },
+            form: *form,
+            archetype: *archetype,
+            health: self.get("health")?,
+            max_health: self.get("max_health")?,
+            damage_factor: self.get("damage")?,
+            armour_factor: self.get("armour")?,
+            player_target_distance: self.get("player_distance")?,
+            goal_target_distance: self.get("goal_distance")?,
+            turret_target_distance: self.get("turret_distance")?,
+            obstacle_target_distance: self.get("obstacle_distance")?,
+            value: self.get("value")?,
+            slowed: self.get_or("slowed", 0)?,
+            reload: self.get_or("reload", 0)?,
+            stuck: self.get_or("stuck", 0)?,
+        })
+    }
+
+    // Arrows saved before their origin was recorded are treated as
+    // fired from where they are.
+    fn arrow(&self, (x, y): (usize, usize)) -> Result<ArrowInfo, String> {
+        Ok(ArrowInfo {
+            dx: self.get("dx")?,
+            dy: self.get("dy")?,
+            dir: (self.get("dir_x")?, self.get("dir_y")?),
+            incx: self.get("incx")?,
+            incy: self.get("incy")?,
+            speed: self.get("speed")?,
+            err: self.get("err")?,
+            err_inc: self.get("err_inc")?,
+            err_dec: self.get("err_dec")?,
+            corrx: self.get("corrx")?,
+            corry: self.get("corry")?,
+            damage_factor: self.get("damage")?,
+            splash: self.get_or("splash", 0)?,
+            slow: self.get_or("slow", 0)?,
+            faction: if self.get_or("fiends", false)? {
+                Faction::Fiends
+            } else {
+                Faction::Defenders
+            },
+            origin: (self.get_or("origin_x", x)?, self.get_or("origin_y", y)?),
+        })
+    }
+}
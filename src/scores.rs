@@ -0,0 +1,229 @@
+//! Scoring finished games, and keeping a table of the best scores.
+//!
+//! The table is plain text: a header line, then one line per entry,
+//! best first, giving the points, the waves survived, the fiends
+//! killed, how much of the Thing was left (as a percentage), and then
+//! the name, which is everything else on the line:
+//!
+//! ```text
+//! ludum_dare_37 scores 1
+//! 2615 21 133 71 Mike
+//! 1210 10 52 42 Nathan van Doorn
+//! ```
+
+use game::Game;
+use model::*;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "ludum_dare_37 scores 1";
+
+/// How many scores the table keeps.
+pub const MAX_ENTRIES: usize = 10;
+
+/// The longest name which can go in the table.
+pub const MAX_NAME_LENGTH: usize = 20;
+
+// What everything is worth.
+const WAVE_POINTS: usize = 100;
+const KILL_POINTS: usize = 10;
+const INTEGRITY_POINTS: usize = 5;
+
+/// How well a game went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Score {
+    pub points: usize,
+    pub waves: usize,
+    pub kills: usize,
+    /// How much of the Thing is left, as a percentage.
+    pub integrity: usize,
+}
+
+impl Score {
+    /// The score for a game as it stands. A wave which is still being
+    /// fought, or which was lost, doesn't count as survived.
+    pub fn of(game: &Game) -> Score {
+        let world_data = &game.world_data;
+        let waves = match game.state {
+            Fight { .. } | GameOver { .. } => world_data.wave.saturating_sub(1),
+            _ => world_data.wave,
+        };
+        let (gx, gy) = world_data.goal_location;
+        let integrity = match world_data.statics[gy][gx] {
+            Some(Goal { health, max_health }) if max_health > 0 => health * 100 / max_health,
+            _ => 0,
+        };
+        Score::new(waves, world_data.kills, integrity)
+    }
+
+    pub fn new(waves: usize, kills: usize, integrity: usize) -> Score {
+        Score {
+            points: waves * WAVE_POINTS + kills * KILL_POINTS + integrity * INTEGRITY_POINTS,
+            waves,
+            kills,
+            integrity,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub score: Score,
+}
+
+/// The best scores so far, best first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HighScores {
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+pub enum ScoresError {
+    Io(io::Error),
+    /// A line of the file didn't make sense.
+    Parse { line: usize, msg: String },
+}
+
+impl fmt::Display for ScoresError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScoresError::Io(ref err) => write!(f, "{}", err),
+            ScoresError::Parse { line, ref msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl From<io::Error> for ScoresError {
+    fn from(err: io::Error) -> ScoresError {
+        ScoresError::Io(err)
+    }
+}
+
+impl HighScores {
+    pub fn read<R: BufRead>(input: R) -> Result<HighScores, ScoresError> {
+        let mut lines = input.lines();
+        if lines.next().unwrap_or_else(|| Ok(String::new()))? != HEADER {
+            return Err(parse_error(1, "not a high score table".to_string()));
+        }
+
+        let mut scores = HighScores::default();
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = read_entry(&line).ok_or_else(|| {
+                    parse_error(n + 2, format!("bad entry: {}", line))
+                })?;
+            scores.add(entry.name, entry.score);
+        }
+        Ok(scores)
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        for entry in &self.entries {
+            let score = &entry.score;
+            writeln!(out,
+                     "{} {} {} {} {}",
+                     score.points,
+                     score.waves,
+                     score.kills,
+                     score.integrity,
+                     entry.name)?;
+        }
+        out.flush()
+    }
+
+    /// Read the table from a file. There being no file yet just means
+    /// there are no scores yet.
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<HighScores, ScoresError> {
+        match File::open(path) {
+            Ok(file) => HighScores::read(BufReader::new(file)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(HighScores::default()),
+            Err(err) => Err(From::from(err)),
+        }
+    }
+
+    /// Write the table to a file, making the directory it goes in if
+    /// need be.
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    /// Whether a score is good enough to go in the table.
+    pub fn qualifies(&self, score: &Score) -> bool {
+        self.entries.len() < MAX_ENTRIES ||
+        self.entries.last().is_some_and(|entry| score.points > entry.score.points)
+    }
+
+    /// Put a score in the table, if it's good enough, and say where
+    /// it went. Ties go below the scores already there.
+    pub fn add(&mut self, name: String, score: Score) -> Option<usize> {
+        if !self.qualifies(&score) {
+            return None;
+        }
+        let rank = self.entries
+            .iter()
+            .position(|entry| score.points > entry.score.points)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, Entry { name: clean_name(&name), score });
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+}
+
+fn read_entry(line: &str) -> Option<Entry> {
+    let mut words = line.trim().splitn(5, ' ');
+    let mut number = || words.next().and_then(|word| word.parse().ok());
+    let (points, waves, kills, integrity) = (number()?, number()?, number()?, number()?);
+    let name = words.next().unwrap_or("").to_string();
+    Some(Entry {
+        name,
+        score: Score {
+            points,
+            waves,
+            kills,
+            integrity,
+        },
+    })
+}
+
+// Keep names to one line of a sensible length, so the table can be
+// read back in and fits on the screen.
+fn clean_name(name: &str) -> String {
+    let name = name.chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+    match name.trim() {
+        "" => "Anonymous".to_string(),
+        name => name.to_string(),
+    }
+}
+
+fn parse_error(line: usize, msg: String) -> ScoresError {
+    ScoresError::Parse { line, msg }
+}
+
+/// Where the high score table is kept: in the user's data directory
+/// (`$XDG_DATA_HOME`, or `~/.local/share`, or `%APPDATA%` on Windows).
+/// None if there's nowhere suitable.
+pub fn default_path() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_dir.join("ludum_dare_37").join("scores.txt"))
+}
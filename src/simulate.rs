@@ -0,0 +1,172 @@
+// The 'simulate' command: how long a fixed layout holds out with
+// nobody playing.
+
+use ludum_dare_37::{Game, Settings};
+use ludum_dare_37::model::*;
+
+use options::Format;
+
+// How many ticks a wave can go on for before it's given up on, in
+// case the fiends are stuck.
+const MAX_WAVE_TICKS: u64 = 10_000;
+
+// How a wave went.
+struct WaveResult {
+    wave: usize,
+    // "survived", "lost" or "stuck".
+    result: &'static str,
+    // Why the game ended, if it did.
+    msg: Option<String>,
+    thing: usize,
+    thing_max: usize,
+    cash: usize,
+    // How many fiends each turret killed, in the order of 'turrets'.
+    kills: Vec<usize>,
+}
+
+/// Play waves against the layout in the settings' map, without
+/// building anything or anyone in the room, until the Thing is
+/// destroyed or 'max_wave' waves are over.
+pub fn simulate(settings: Settings, max_wave: usize, format: Format) {
+    let mut game = Game::new(settings);
+    game.command(Command::Select);
+    // Nobody is playing, so an idle player would only be in the way.
+    game.world_data.remove_player();
+    // Turrets are never moved, so they're always in the same order.
+    let turrets = game.world_data.turrets.iter().cloned().collect::<Vec<_>>();
+
+    let mut results = Vec::new();
+    while game.world_data.wave < max_wave {
+        let cash_before = game.world_data.cash;
+        let kills_before = turret_kills(&game.world_data, &turrets);
+        game.command(Command::Continue);
+
+        let started = game.ticks;
+        while let Fight { .. } = game.state {
+            if game.ticks - started >= MAX_WAVE_TICKS {
+                break;
+            }
+            game.tick();
+        }
+
+        let world_data = &game.world_data;
+        let (result, msg) = match game.state {
+            Construct { .. } => ("survived", None),
+            GameOver { ref msg } => ("lost", Some(msg.clone())),
+            _ => ("stuck", Some("The wave never ended!".to_string())),
+        };
+        let (thing, thing_max) = match world_data.statics[world_data.goal_location.1]
+                                                       [world_data.goal_location.0] {
+            Some(Goal { health, max_health }) => (health, max_health),
+            _ => (0, 0),
+        };
+        let kills = turret_kills(world_data, &turrets)
+            .iter()
+            .zip(kills_before)
+            .map(|(after, before)| after - before)
+            .collect();
+        results.push(WaveResult {
+            wave: world_data.wave,
+            result,
+            msg,
+            thing,
+            thing_max,
+            cash: world_data.cash.saturating_sub(cash_before),
+            kills,
+        });
+        if result != "survived" {
+            break;
+        }
+    }
+
+    let names = turrets.iter()
+        .map(|&(x, y)| match game.world_data.statics[y][x] {
+            Some(Turret { info }) => info.form.name(),
+            _ => "?",
+        })
+        .collect::<Vec<_>>();
+    match format {
+        Format::Text => print_text(&results, &turrets, &names),
+        Format::Csv => print_csv(&results, &turrets),
+        Format::Json => print_json(&results, &turrets, &names),
+    }
+}
+
+fn turret_kills(world_data: &WorldData, turrets: &[(usize, usize)]) -> Vec<usize> {
+    turrets.iter()
+        .map(|&(x, y)| match world_data.statics[y][x] {
+            Some(Turret { info }) => info.kills,
+            _ => 0,
+        })
+        .collect()
+}
+
+fn join(kills: &[usize], sep: &str) -> String {
+    kills.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(sep)
+}
+
+fn print_text(results: &[WaveResult], turrets: &[(usize, usize)], names: &[&str]) {
+    for r in results {
+        let msg = match r.msg {
+            Some(ref msg) => format!(" {}", msg),
+            None => String::new(),
+        };
+        println!("Wave {}: {}.{} Thing {}/{}, cash +{}, kills {} ({})",
+                 r.wave,
+                 r.result,
+                 msg,
+                 r.thing,
+                 r.thing_max,
+                 r.cash,
+                 r.kills.iter().sum::<usize>(),
+                 join(&r.kills, ", "));
+    }
+    println!("Kills per turret:");
+    for (i, (&(x, y), name)) in turrets.iter().zip(names).enumerate() {
+        let total = results.iter().map(|r| r.kills[i]).sum::<usize>();
+        println!("  {} at ({}, {}): {}", name, x, y, total);
+    }
+}
+
+fn print_csv(results: &[WaveResult], turrets: &[(usize, usize)]) {
+    let columns = turrets.iter()
+        .map(|&(x, y)| format!(",turret_{}_{}", x, y))
+        .collect::<String>();
+    println!("wave,result,thing,thing_max,cash,kills{}", columns);
+    for r in results {
+        println!("{},{},{},{},{},{}{}",
+                 r.wave,
+                 r.result,
+                 r.thing,
+                 r.thing_max,
+                 r.cash,
+                 r.kills.iter().sum::<usize>(),
+                 r.kills.iter().map(|k| format!(",{}", k)).collect::<String>());
+    }
+}
+
+fn print_json(results: &[WaveResult], turrets: &[(usize, usize)], names: &[&str]) {
+    let turrets = turrets.iter()
+        .zip(names)
+        .map(|(&(x, y), name)| format!("{{\"x\": {}, \"y\": {}, \"form\": \"{}\"}}", x, y, name))
+        .collect::<Vec<_>>();
+    println!("{{\n  \"turrets\": [{}],\n  \"waves\": [", turrets.join(", "));
+    for (i, r) in results.iter().enumerate() {
+        let msg = match r.msg {
+            Some(ref msg) => format!("\"{}\"", msg),
+            None => "null".to_string(),
+        };
+        println!("    {{\"wave\": {}, \"result\": \"{}\", \"msg\": {}, \"thing\": {}, \
+                  \"thing_max\": {}, \"cash\": {}, \"kills\": {}, \"turret_kills\": [{}]}}{}",
+                 r.wave,
+                 r.result,
+                 msg,
+                 r.thing,
+                 r.thing_max,
+                 r.cash,
+                 r.kills.iter().sum::<usize>(),
+                 join(&r.kills, ", "),
+                 if i + 1 == results.len() { "" } else { "," });
+    }
+    println!("  ]\n}}");
+}
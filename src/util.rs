@@ -1,5 +1,3 @@
-use model::*;
-
 use std::cmp::{min, max};
 use std::collections::BTreeSet;
 use std::ops::Sub;
@@ -25,7 +23,7 @@ pub fn find_nearest(points: &BTreeSet<(usize, usize)>,
         }
     }
 
-    return found_xy;
+    found_xy
 }
 
 // implements Chebyshev distance https://en.wikipedia.org/wiki/Chebyshev_distance
@@ -35,32 +33,42 @@ pub fn distance<T>((x1, y1): (T, T), (x2, y2): (T, T)) -> T::Output
 {
     let dx = max(x1, x2) - min(x1, x2);
     let dy = max(y1, y2) - min(y1, y2);
-    return max(dx, dy);
+    max(dx, dy)
 }
 
-pub fn adjacency((x, y): (usize, usize)) -> Vec<(usize, usize)> {
-    let mut adj = Vec::new();
-    for i in 0..3 {
-        if (i == 0 && x == 0) || (i == 2 && x == X - 1) {
-            continue;
-        }
-        let cx = match i {
-            0 => x - 1,
-            2 => x + 1,
-            _ => x,
-        };
-        for j in 0..3 {
-            if (j == 0 && y == 0) || (j == 2 && y == Y - 1) || (i == 1 && j == 1) ||
-               (i != 1 && j != 1) {
-                continue;
-            }
-            let cy = match j {
-                0 => y - 1,
-                2 => y + 1,
-                _ => y,
-            };
-            adj.push((cx, cy));
-        }
-    }
-    adj
+/// How much armour it takes to halve incoming damage.
+pub const ARMOUR_SCALE: usize = 100;
+
+/// How much damage an attack actually does once armour is taken into
+/// account. Every attack on the player or a fiend goes through this:
+///
+/// ```text
+/// damage * ARMOUR_SCALE / (ARMOUR_SCALE + armour)
+/// ```
+///
+/// So 100 armour halves damage, 300 quarters it, and so on. Every hit
+/// does at least 1 damage, however well armoured the target.
+pub fn resolve_damage(damage: usize, armour: usize) -> usize {
+    max(1, damage * ARMOUR_SCALE / (ARMOUR_SCALE + armour))
+}
+
+/// The spaces next to (x, y) in a room of the given size.
+pub fn adjacency((x, y): (usize, usize),
+                 (width, height): (usize, usize))
+                 -> impl Iterator<Item = (usize, usize)> {
+    // Working these out without allocating matters, as pathfinding
+    // does it for every space in the room.
+    let spaces = [(x > 0, (x.wrapping_sub(1), y)),
+                  (y > 0, (x, y.wrapping_sub(1))),
+                  (y + 1 < height, (x, y + 1)),
+                  (x + 1 < width, (x + 1, y))];
+    IntoIterator::into_iter(spaces).filter(|&(inside, _)| inside).map(|(_, xy)| xy)
+}
+
+/// Parse a room size written as 'WIDTHxHEIGHT'.
+pub fn parse_size(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.splitn(2, 'x');
+    let width = parts.next()?.parse().ok()?;
+    let height = parts.next()?.parse().ok()?;
+    Some((width, height))
 }
@@ -1,22 +1,38 @@
-use model::*;
+use ludum_dare_37::model::*;
+use ludum_dare_37::scores::{HighScores, Score};
 
 use pancurses::*;
 
-type color_pair = u32;
-const DEFAULT_COLORS: color_pair = 0;
-const GOAL_COLORS: color_pair = 1;
-const BROKEN_TURRET_COLORS: color_pair = 2;
-const DAMAGED_TURRET_COLORS: color_pair = 3;
-const PLACEMENT_COLORS: color_pair = 4;
-const GAMEOVER_COLORS: color_pair = 5;
+use std::cmp::{max, min};
+
+type ColorPair = u32;
+const DEFAULT_COLORS: ColorPair = 0;
+const GOAL_COLORS: ColorPair = 1;
+const BROKEN_TURRET_COLORS: ColorPair = 2;
+const DAMAGED_TURRET_COLORS: ColorPair = 3;
+const PLACEMENT_COLORS: ColorPair = 4;
+const GAMEOVER_COLORS: ColorPair = 5;
+const UPGRADED_TURRET_COLORS: ColorPair = 6;
+const ELITE_TURRET_COLORS: ColorPair = 7;
+const FIEND_ARROW_COLORS: ColorPair = 8;
 
 const EMPTY_CELL: chtype = ' ' as u32;
 
+// The fixed parts of the layout. The view of the room gets whatever
+// space is left, and scrolls if the room doesn't fit.
+const STATS_HEIGHT: i32 = 5;
+const LOG_HEIGHT: i32 = 7;
+const HELP_WIDTH: i32 = 17;
+// Wide enough for the stats.
+const MIN_VIEW_WIDTH: i32 = 63;
+
 pub struct GameWindows {
     stats: Window,
     view: Window,
     help: Window,
     log: Window,
+    // The top-left corner of the part of the room in view.
+    camera: (usize, usize),
 }
 
 impl GameWindows {
@@ -28,7 +44,9 @@ impl GameWindows {
     }
 }
 
-pub fn setup_render(window: &Window) -> GameWindows {
+/// Lay the windows out to fit the terminal and the room. This needs
+/// doing again whenever the terminal is resized.
+pub fn setup_render(window: &Window, world_data: &WorldData) -> GameWindows {
     start_color();
     use_default_colors();
     init_pair(DEFAULT_COLORS as i16, COLOR_WHITE, -1);
@@ -37,72 +55,113 @@ pub fn setup_render(window: &Window) -> GameWindows {
     init_pair(DAMAGED_TURRET_COLORS as i16, COLOR_MAGENTA, -1);
     init_pair(PLACEMENT_COLORS as i16, COLOR_BLUE, -1);
     init_pair(GAMEOVER_COLORS as i16, COLOR_RED, -1);
+    init_pair(UPGRADED_TURRET_COLORS as i16, COLOR_CYAN, -1);
+    init_pair(ELITE_TURRET_COLORS as i16, COLOR_GREEN, -1);
+    init_pair(FIEND_ARROW_COLORS as i16, COLOR_RED, -1);
+
+    let (rows, cols) = window.get_max_yx();
+    let width = max(1, min(max(world_data.width() as i32, MIN_VIEW_WIDTH), cols - HELP_WIDTH));
+    let height = max(1, min(world_data.height() as i32, rows - STATS_HEIGHT - LOG_HEIGHT));
 
-    let stats = window.subwin(5, X as i32, 0, 0).unwrap();
+    window.clear();
+    window.refresh();
+    let stats = window.subwin(STATS_HEIGHT, width, 0, 0).unwrap();
     stats.keypad(true);
     stats.draw_box(0, 0);
-    let view = window.subwin(Y as i32, X as i32, 5, 0).unwrap();
+    let view = window.subwin(height, width, STATS_HEIGHT, 0).unwrap();
     view.keypad(true);
-    let help = window.subwin(5 + Y as i32 + 7, 80 - X as i32, 0, X as i32).unwrap();
+    let help = window.subwin(STATS_HEIGHT + height + LOG_HEIGHT, HELP_WIDTH, 0, width).unwrap();
     help.draw_box(0, 0);
     help.keypad(true);
-    let log = window.subwin(7, X as i32, 5 + Y as i32, 0).unwrap();
+    let log = window.subwin(LOG_HEIGHT, width, STATS_HEIGHT + height, 0).unwrap();
     log.draw_box(0, 0);
     log.keypad(true);
-    return GameWindows {
-        stats: stats,
-        view: view,
-        help: help,
-        log: log,
-    };
+    GameWindows {
+        stats,
+        view,
+        help,
+        log,
+        camera: (0, 0),
+    }
 }
 
-impl WorldData {
-    pub fn render(&self, windows: &GameWindows, game_state: &GameState) {
-        self.render_frame(windows);
+impl GameWindows {
+    pub fn render(&mut self, world_data: &WorldData, game_state: &GameState) {
+        // Keep whatever the player is moving around in view.
+        let focus = match *game_state {
+            Construct { menu: Menu::Place(_, location, _), .. } => location,
+            Construct { menu: Menu::UpgradeTurret(xy), .. } => xy,
+            _ => world_data.player_info.location,
+        };
+        let (rows, cols) = self.view.get_max_yx();
+        self.camera = (scroll(focus.0, cols as usize, world_data.width()),
+                       scroll(focus.1, rows as usize, world_data.height()));
+
+        self.render_frame(world_data);
         match *game_state {
-            Startup => self.render_startup(windows),
-            Construct { menu, menu_index } => self.render_construct(windows, menu, menu_index),
-            Fight { .. } => self.render_fight(windows),
-            GameOver { ref msg } => self.render_gameover(windows, msg),
-            _ => unimplemented!(),
+            Startup => self.render_startup(world_data.difficulty),
+            Construct { menu, menu_index } => self.render_construct(world_data, menu, menu_index),
+            Fight { .. } => self.render_fight(world_data),
+            GameOver { ref msg } => self.render_gameover(msg),
+            // The game is over and about to close, so there's nothing
+            // more to show.
+            End => (),
         };
-        windows.refresh();
+        self.refresh();
     }
 
-    fn render_frame(&self, windows: &GameWindows) {
-        windows.help.erase();
-        windows.help.draw_box(0, 0);
-        windows.help.mvaddstr(1, 1, "THING PROTECTOR");
+    fn render_frame(&self, world_data: &WorldData) {
+        self.help.erase();
+        self.help.draw_box(0, 0);
+        self.help.mvaddstr(1, 1, "THING PROTECTOR");
 
         let stat_string1 = format!("Health: {:3} | Thing Integrity: {:3} | Wave: {:3}",
-                                   self.player_info.health,
-                                   match self.statics[Y / 2][X / 2] {
+                                   world_data.player_info.health,
+                                   match world_data.statics[world_data.goal_location.1]
+                                                           [world_data.goal_location.0] {
                                        Some(Goal { health: h, .. }) => h,
                                        _ => 0,
                                    },
-                                   self.wave);
-        let stat_string2 = format!("Cash: {:5}", self.cash);
+                                   world_data.wave);
+        let stat_string2 = format!("Cash: {:5} | Difficulty: {}",
+                                   world_data.cash,
+                                   world_data.difficulty.name());
 
-        let offset = (X - stat_string1.len()) as i32 / 2;
-        windows.stats.mvaddstr(2, offset, stat_string1.as_str());
-        windows.stats.mvaddstr(3, offset, stat_string2.as_str());
+        // Everything is drawn afresh, in case a popup was covering it.
+        self.stats.erase();
+        self.stats.draw_box(0, 0);
+        let offset = max(0, self.stats.get_max_x() - stat_string1.len() as i32) / 2;
+        self.stats.mvaddstr(2, offset, stat_string1.as_str());
+        self.stats.mvaddstr(3, offset, stat_string2.as_str());
 
-        windows.log.clear();
-        windows.log.draw_box(0, 0);
-        for i in 0..self.log.len() {
-            windows.log.mvaddstr(i as i32 + 1, 1, self.log[i].as_str());
+        self.log.clear();
+        self.log.draw_box(0, 0);
+        for i in 0..world_data.log.len() {
+            self.log.mvaddstr(i as i32 + 1, 1, world_data.log[i].as_str());
         }
 
-        for row_n in 0..Y {
-            for col_n in 0..X {
-                let ch = self.statics[row_n][col_n].map_or(EMPTY_CELL, |s| s.render(row_n));
-                windows.view.mvaddch(row_n as i32, col_n as i32, ch);
+        self.view.erase();
+        for (row_n, row) in world_data.statics.rows().enumerate() {
+            for (col_n, sta) in row.iter().enumerate() {
+                let ch = match *sta {
+                    Some(Gate) => gate_ch(world_data, (col_n, row_n)),
+                    Some(ref sta) => static_ch(sta),
+                    None => EMPTY_CELL,
+                };
+                self.put((col_n, row_n), ch);
             }
         }
     }
 
-    fn render_startup(&self, windows: &GameWindows) {
+    // Draw something at a place in the room, if it's in view.
+    fn put(&self, (x, y): (usize, usize), ch: chtype) {
+        let (cx, cy) = self.camera;
+        if x >= cx && y >= cy {
+            self.view.mvaddch((y - cy) as i32, (x - cx) as i32, ch);
+        }
+    }
+
+    fn render_startup(&self, difficulty: Difficulty) {
         let message = "              You are in a room.\n\nThe Thing is also in the room. It is \
                        holy to you.\nFoul fiends endevour even as we speak to destroy\nthe Thing. \
                        You must protect it with all your might!\n\nYou can defend the Thing by \
@@ -111,190 +170,372 @@ impl WorldData {
                        move, and space or return\nto select items in menus. Your forsworn fight \
                        begins!";
         let max_line_length = message.lines().max_by_key(|line| line.len()).unwrap().len();
+        let message = format!("{}\n\n{:^width$}\n{:^width$}\n{:^width$}",
+                              message,
+                              format!("Difficulty: < {} >", difficulty.name()),
+                              "Left and right pick the difficulty.",
+                              "Press H to see the high scores.",
+                              width = max_line_length);
         let lines_count = message.lines().count();
         for line in message.lines().enumerate() {
             let (row, line) = line;
-            windows.view.mvaddstr((row + (Y - lines_count) / 2) as i32,
-                                  ((X - max_line_length) / 2) as i32,
-                                  line);
+            let (rows, cols) = self.view.get_max_yx();
+            self.view.mvaddstr(row as i32 + max(0, rows - lines_count as i32) / 2,
+                               max(0, cols - max_line_length as i32) / 2,
+                               line);
         }
     }
 
-    fn render_construct(&self, windows: &GameWindows, menu: Menu, menu_index: usize) {
+    fn render_construct(&self, world_data: &WorldData, menu: Menu, menu_index: usize) {
         match menu {
             Menu::Root => {
-                windows.help.mvaddstr(3, 3, "Build");
-                windows.help.mvaddstr(4, 3, "Move");
-                windows.help.mvaddstr(5, 3, "Continue");
-                windows.help.mvaddch(menu_index as i32 + 3, 2, '>');
-                windows.help.mvaddch(menu_index as i32 + 3, 13, '<');
+                for (i, item) in ROOT_ITEMS.iter().enumerate() {
+                    self.help.mvaddstr(i as i32 + 3, 3, item.name());
+                }
+                self.help.mvaddch(menu_index as i32 + 3, 2, '>');
+                self.help.mvaddch(menu_index as i32 + 3, 13, '<');
             }
             Menu::Build => {
-                windows.help.mvaddstr(3, 3, "Turret");
-                windows.help.mvaddstr(4, 3, "Obstacle");
-                windows.help.mvaddstr(5, 3, "Back");
-                windows.help.mvaddch(menu_index as i32 + 3, 2, '>');
-                windows.help.mvaddch(menu_index as i32 + 3, 13, '<');
-            }
-
-            Menu::Move(depth) => {
-                // we want to display Y - 2 (border) - 3 (title) rows
-                // and we have 1 + self.turrets.len() items
-                let turrets = self.turrets.iter().enumerate().skip(depth);
-                let nturrets = turrets.len();
-                let y = Y + 5 + 7 - 5;
-                for item in turrets.take(y) {
-                    let (i, s) = item;
-                    windows.help.mvaddstr((i - depth) as i32 + 3,
-                                          3,
-                                          format!("Turret {}", i + 1).as_str());
-                    if i - depth == menu_index {
-                        let placement = self.statics[s.1][s.0].unwrap();
-                        windows.view.mvaddch(s.1 as i32,
-                                             s.0 as i32,
-                                             placement.render(1) | COLOR_PAIR(PLACEMENT_COLORS));
-                    }
+                for (i, buildable) in BUILDABLES.iter().enumerate() {
+                    let item = format!("{:8} {:3}", buildable.name(), buildable.cost());
+                    self.help.mvaddstr(i as i32 + 3, 3, &item);
                 }
-                if nturrets <= depth {
-                    let obstacles = self.obstacles.iter().enumerate().skip(depth - nturrets);
-                    for item in obstacles.take(y) {
-                        let (i, s) = item;
-                        windows.help.mvaddstr((i - nturrets) as i32 + 3,
-                                              3,
-                                              format!("Obstacle {}", i + 1).as_str());
-                        if i - nturrets == menu_index {
-                            let placement = self.statics[s.1][s.0].unwrap();
-                            windows.view.mvaddch(s.1 as i32,
-                                                 s.0 as i32,
-                                                 placement.render(1) |
-                                                 COLOR_PAIR(PLACEMENT_COLORS));
-                        }
+                self.help.mvaddstr(BUILDABLES.len() as i32 + 3, 3, "Back");
+                self.help.mvaddch(menu_index as i32 + 3, 2, '>');
+                self.help.mvaddch(menu_index as i32 + 3, 15, '<');
+            }
 
-                    }
-                } else if nturrets > depth + y {
+            Menu::Move => {
+                let turrets = world_data.turrets
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &xy)| (format!("Turret {}", i + 1), xy));
+                let obstacles = world_data.obstacles
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &xy)| (format!("Obstacle {}", i + 1), xy));
+                let items = turrets.chain(obstacles).collect::<Vec<_>>();
+                self.render_placed(world_data, &items, menu_index, 13);
+            }
 
-                } else {
-                    let obstacles = self.obstacles.iter().enumerate();
-                    for item in obstacles.take(depth + y - nturrets) {
-                        let (i, s) = item;
-                        windows.help.mvaddstr(i as i32 + nturrets as i32 + 3,
-                                              3,
-                                              format!("Obstacle {}", i + 1).as_str());
-                        if i + nturrets == menu_index {
-                            let placement = self.statics[s.1][s.0].unwrap();
-                            windows.view.mvaddch(s.1 as i32,
-                                                 s.0 as i32,
-                                                 placement.render(1) |
-                                                 COLOR_PAIR(PLACEMENT_COLORS));
-                        }
+            Menu::Upgrade => {
+                let items = world_data.turrets
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(x, y))| {
+                        let item = match world_data.statics[y][x] {
+                            Some(Turret { info }) => {
+                                format!("{:6} {:2} L{}", info.form.name(), i + 1, info.level())
+                            }
+                            _ => format!("Turret {:2}", i + 1),
+                        };
+                        (item, (x, y))
+                    })
+                    .collect::<Vec<_>>();
+                self.render_placed(world_data, &items, menu_index, 15);
+            }
 
+            Menu::UpgradeTurret((x, y)) => {
+                let sta = world_data.statics[y][x].unwrap();
+                if let Turret { info } = sta {
+                    for (i, stat) in TURRET_STATS.iter().enumerate() {
+                        let cost = match stat.cost(&info) {
+                            Some(cost) => format!("{:3}", cost),
+                            None => "max".to_string(),
+                        };
+                        let item = format!("{:6} {} {}", stat.name(), info.tiers[i], cost);
+                        self.help.mvaddstr(i as i32 + 3, 3, &item);
                     }
-                };
-                let break_point = self.turrets.len() + self.obstacles.len() - depth;
-                if break_point < y {
-                    windows.help.mvaddstr(break_point as i32 + 3, 3, "Back");
+                    let n = TURRET_STATS.len() as i32;
+                    self.help.mvaddstr(n + 3, 3, &format!("Aim: {}", info.targeting.name()));
+                    self.help.mvaddstr(n + 4, 3, "Back");
+                    self.help.mvaddstr(n + 6,
+                                       3,
+                                       &format!("{} L{}", info.form.name(), info.level()));
+                    self.help.mvaddstr(n + 7,
+                                       3,
+                                       &format!("HP {}/{}", info.health, info.max_health));
+                }
+                self.help.mvaddch(menu_index as i32 + 3, 2, '>');
+                self.help.mvaddch(menu_index as i32 + 3, 15, '<');
+                self.put((x, y), static_ch(&sta) | COLOR_PAIR(PLACEMENT_COLORS));
+            }
+
+            Menu::Train => {
+                let info = world_data.player_info;
+                for (i, stat) in PLAYER_STATS.iter().enumerate() {
+                    let cost = match stat.cost(&info) {
+                        Some(cost) => format!("{:3}", cost),
+                        None => "max".to_string(),
+                    };
+                    let item = format!("{:6} {} {}", stat.name(), info.tiers[i], cost);
+                    self.help.mvaddstr(i as i32 + 3, 3, &item);
+                }
+                self.help.mvaddstr(PLAYER_STATS.len() as i32 + 3, 3, "Back");
+                let stats = [format!("HP {}/{}", info.health, info.max_health),
+                             format!("Damage {}", info.damage_factor),
+                             format!("Heal   {}", info.heal_factor),
+                             format!("Armour {}", info.armour_factor)];
+                for (i, line) in stats.iter().enumerate() {
+                    self.help.mvaddstr((PLAYER_STATS.len() + i) as i32 + 5, 3, line);
                 }
-                windows.help.mvaddch(menu_index as i32 + 3, 2, '>');
-                windows.help.mvaddch(menu_index as i32 + 3, 13, '<');
+                self.help.mvaddch(menu_index as i32 + 3, 2, '>');
+                self.help.mvaddch(menu_index as i32 + 3, 15, '<');
             }
 
-            Menu::Place(placement, location) => {
-                windows.help.mvaddstr(3, 3, "Placing a");
-                windows.help.mvaddstr(4,
-                                      3,
-                                      match placement {
-                                          Turret { .. } => "Turret",
-                                          Obstacle { .. } => "Obstacle",
-                                          _ => "Error",
-                                      });
-                windows.view.mvaddch(location.1 as i32,
-                                     location.0 as i32,
-                                     placement.render(1) | COLOR_PAIR(PLACEMENT_COLORS));
+            Menu::Place(placement, location, cost) => {
+                self.help.mvaddstr(3, 3, "Placing a");
+                self.help.mvaddstr(4,
+                                   3,
+                                   match placement {
+                                       Turret { info } => info.form.name(),
+                                       Obstacle { .. } => "Obstacle",
+                                       _ => "Error",
+                                   });
+                if cost > 0 {
+                    self.help.mvaddstr(6, 3, &format!("Cost: {}", cost));
+                }
+                if let Turret { info } = placement {
+                    self.help.mvaddstr(8, 3, &format!("Aim: {}", info.targeting.name()));
+                    self.help.mvaddstr(9, 3, "(T to change)");
+                }
+                self.put(location, static_ch(&placement) | COLOR_PAIR(PLACEMENT_COLORS));
+            }
+        }
+    }
 
+    // A menu of things in the room, followed by "Back", with the
+    // selected thing picked out in the room. The menu scrolls so that
+    // the selected item is always in view.
+    fn render_placed(&self,
+                     world_data: &WorldData,
+                     items: &[(String, (usize, usize))],
+                     menu_index: usize,
+                     right: i32) {
+        // The items go between the title and the bottom of the box.
+        let rows = max(1, self.help.get_max_y() - 4) as usize;
+        let skip = (menu_index + 1).saturating_sub(rows);
+        for (i, &(ref item, xy)) in items.iter().enumerate().skip(skip).take(rows) {
+            self.help.mvaddstr((i - skip) as i32 + 3, 3, item);
+            if i == menu_index {
+                let placement = world_data.statics[xy.1][xy.0].unwrap();
+                self.put(xy, static_ch(&placement) | COLOR_PAIR(PLACEMENT_COLORS));
             }
         }
+        if items.len() - skip < rows {
+            self.help.mvaddstr((items.len() - skip) as i32 + 3, 3, "Back");
+        }
+        self.help.mvaddch((menu_index - skip) as i32 + 3, 2, '>');
+        self.help.mvaddch((menu_index - skip) as i32 + 3, right, '<');
     }
 
-    fn render_fight(&self, windows: &GameWindows) {
-        for row_n in 0..Y {
-            for col_n in 0..X {
-                match self.mobiles[row_n][col_n] {
-                    Some(mob) => {
-                        windows.view.mvaddch(row_n as i32, col_n as i32, mob.render());
-                    }
-                    None => {}
+    fn render_fight(&self, world_data: &WorldData) {
+        for (row_n, row) in world_data.mobiles.rows().enumerate() {
+            for (col_n, mob) in row.iter().enumerate() {
+                if let Some(ref mob) = *mob {
+                    self.put((col_n, row_n), mobile_ch(mob));
                 }
             }
         }
     }
 
-    fn render_gameover(&self, windows: &GameWindows, msg: &String) {
-        let x = (X - msg.len() - 2) as i32 / 2;
-        let y = (Y - 3) as i32 / 2 + 5;
-        let gameover = windows.view.subwin(3, msg.len() as i32 + 2, y, x).unwrap();
+    fn render_gameover(&self, msg: &str) {
+        let (rows, cols) = self.view.get_max_yx();
+        let (top, left) = self.view.get_beg_yx();
+        // Narrow views only get as much of the message as fits, and
+        // views too small for the box don't get it at all.
+        let msg = msg.chars().take(max(0, cols - 2) as usize).collect::<String>();
+        let width = msg.chars().count() as i32 + 2;
+        let x = left + max(0, cols - width) / 2;
+        let y = top + max(0, rows - 3) / 2;
+        let gameover = match self.view.subwin(3, width, y, x) {
+            Ok(gameover) => gameover,
+            Err(_) => return,
+        };
 
         gameover.attron(COLOR_PAIR(GAMEOVER_COLORS));
         gameover.draw_box(0, 0);
         gameover.attron(A_BOLD);
-        gameover.mvaddstr(1, 1, msg.as_str());
+        gameover.mvaddstr(1, 1, &msg);
         gameover.attroff(A_BOLD | COLOR_PAIR(GAMEOVER_COLORS));
 
         gameover.refresh();
     }
-}
 
-impl Mobile {
-    fn render(&self) -> chtype {
-        match *self {
-            Player => '@'.to_chtype(),
-            Fiend { info } => info.ch,
-            Arrow { info: ArrowInfo { dx, dy, dir, .. } } => {
-                if (dx as f64) < 0.3 * dy as f64 {
-                        '|'
-                    } else if (dy as f64) < 0.3 * dx as f64 {
-                        '-'
-                    } else if dir.0 == dir.1 {
-                        '\\'
-                    } else {
-                        '/'
-                    }
-                    .to_chtype()
-            }
+    /// Show the high score table over the room, under some lines of
+    /// explanation, with one of the entries picked out.
+    pub fn render_scores(&self, heading: &[String], scores: &HighScores, picked: Option<usize>) {
+        let mut lines = heading.to_vec();
+        lines.push(String::new());
+        lines.push(format!("{:3} {:<20} {:>6} {:>4} {:>5} {:>5}",
+                           "#",
+                           "Name",
+                           "Score",
+                           "Wave",
+                           "Kills",
+                           "Thing"));
+        for (i, entry) in scores.entries.iter().enumerate() {
+            let score = &entry.score;
+            lines.push(format!("{:2}. {:<20} {:6} {:4} {:5} {:4}%",
+                               i + 1,
+                               entry.name,
+                               score.points,
+                               score.waves,
+                               score.kills,
+                               score.integrity));
+        }
+        if scores.entries.is_empty() {
+            lines.push("No scores yet.".to_string());
+        }
+        self.popup(&lines, heading.len(), picked.map(|i| heading.len() + 2 + i));
+    }
+
+    /// Ask for a name to put in the high score table, showing what's
+    /// been typed so far.
+    pub fn render_name_entry(&self, msg: &str, score: &Score, name: &str) {
+        let lines = [msg.to_string(),
+                     describe(score),
+                     String::new(),
+                     "A new high score! Enter your name:".to_string(),
+                     format!("> {}_", name)];
+        self.popup(&lines, 1, None);
+    }
+
+    // Draw lines of text in a box in the middle of everything left of
+    // the help, so that it has room even if the room is small. The
+    // first few lines are a heading, in bold, and one other line can
+    // be picked out.
+    fn popup(&self, lines: &[String], heading: usize, picked: Option<usize>) {
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32 + 4;
+        let height = lines.len() as i32 + 2;
+        let cols = self.view.get_max_x();
+        let rows = self.log.get_beg_y() + self.log.get_max_y();
+        let popup = newwin(min(height, rows),
+                           min(width, cols),
+                           max(0, rows - height) / 2,
+                           max(0, cols - width) / 2);
+
+        popup.erase();
+        popup.attron(COLOR_PAIR(GAMEOVER_COLORS));
+        popup.draw_box(0, 0);
+        popup.attroff(COLOR_PAIR(GAMEOVER_COLORS));
+        for (i, line) in lines.iter().enumerate() {
+            let attributes = if i < heading {
+                A_BOLD | COLOR_PAIR(GAMEOVER_COLORS)
+            } else if Some(i) == picked {
+                A_REVERSE
+            } else {
+                A_NORMAL
+            };
+            popup.attron(attributes);
+            popup.mvaddstr(i as i32 + 1, 2, line);
+            popup.attroff(attributes);
         }
+        popup.refresh();
     }
 }
 
-impl Static {
-    fn render(&self, row_n: usize) -> chtype {
-        let chty = match *self {
-                Wall => '#',
-                Gate => {
-                    if row_n == 0 || row_n == Y - 1 {
-                        '-'
-                    } else {
-                        '|'
-                    }
+/// What a score was made up of, in a line.
+pub fn describe(score: &Score) -> String {
+    format!("Score: {} ({} waves, {} kills, Thing {}%)",
+            score.points,
+            score.waves,
+            score.kills,
+            score.integrity)
+}
+
+fn mobile_ch(mob: &Mobile) -> chtype {
+    match *mob {
+        Player => '@'.to_chtype(),
+        Fiend { info } => glyph_ch(info.glyph),
+        Arrow { info: ArrowInfo { dx, dy, dir, faction, .. } } => {
+            let chty = if (dx as f64) < 0.3 * dy as f64 {
+                    '|'
+                } else if (dy as f64) < 0.3 * dx as f64 {
+                    '-'
+                } else if dir.0 == dir.1 {
+                    '\\'
+                } else {
+                    '/'
                 }
-                Goal { .. } => 'Y',
-                Turret { .. } => 'O',
-                Obstacle { .. } => '=',
+                .to_chtype();
+            match faction {
+                Faction::Defenders => chty,
+                Faction::Fiends => chty | COLOR_PAIR(FIEND_ARROW_COLORS),
             }
-            .to_chtype();
+        }
+    }
+}
+
+// Gates in walls running across the room are drawn across.
+fn gate_ch(world_data: &WorldData, (x, y): (usize, usize)) -> chtype {
+    let row = &world_data.statics[y];
+    let across = [x.wrapping_sub(1), x + 1]
+        .iter()
+        .any(|&nx| matches!(row.get(nx), Some(&Some(Wall)) | Some(&Some(Gate))));
+    if across { '-' } else { '|' }.to_chtype()
+}
 
-        // Apply formatting
-        match *self {
-            Goal { .. } => chty | COLOR_PAIR(GOAL_COLORS),
+// Gates are drawn by 'gate_ch', as it depends on where they are.
+fn static_ch(sta: &Static) -> chtype {
+    let chty = match *sta {
+            Wall => '#',
+            Gate => '|',
+            Goal { .. } => 'Y',
             Turret { info } => {
-                let colour = if info.health == 0 {
-                    BROKEN_TURRET_COLORS
-                } else if info.health <= info.max_health / 2 {
-                    DAMAGED_TURRET_COLORS
-                } else {
-                    DEFAULT_COLORS
-                };
-                chty | A_BOLD | COLOR_PAIR(colour)
+                match info.form {
+                    TurretForm::Basic => 'O',
+                    TurretForm::Rapid => '%',
+                    TurretForm::Sniper => '!',
+                    TurretForm::Mortar => '&',
+                    TurretForm::Frost => '*',
+                }
             }
-            _ => chty,
+            Obstacle { .. } => '=',
+        }
+        .to_chtype();
+
+    // Apply formatting
+    match *sta {
+        Goal { .. } => chty | COLOR_PAIR(GOAL_COLORS),
+        Turret { info } => {
+            let colour = if info.health == 0 {
+                BROKEN_TURRET_COLORS
+            } else if info.health <= info.max_health / 2 {
+                DAMAGED_TURRET_COLORS
+            } else if is_elite(&info) {
+                ELITE_TURRET_COLORS
+            } else if info.level() > 0 {
+                UPGRADED_TURRET_COLORS
+            } else {
+                DEFAULT_COLORS
+            };
+            // Turrets with most of their upgrades look the part.
+            let underline = if is_elite(&info) { A_UNDERLINE } else { 0 };
+            chty | A_BOLD | underline | COLOR_PAIR(colour)
         }
+        _ => chty,
+    }
+}
+
+// Where to put the camera so that 'focus' is in the middle of the
+// view, without going past the edge of the room.
+fn scroll(focus: usize, view: usize, room: usize) -> usize {
+    if room <= view {
+        0
+    } else {
+        min(focus.saturating_sub(view / 2), room - view)
+    }
+}
+
+fn is_elite(info: &TurretInfo) -> bool {
+    info.level() > TURRET_STATS.len() * MAX_TIER as usize / 2
+}
+
+fn glyph_ch(glyph: Glyph) -> chtype {
+    if glyph.bold {
+        glyph.ch.to_chtype() | A_BOLD
+    } else {
+        glyph.ch.to_chtype()
     }
 }
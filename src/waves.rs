@@ -0,0 +1,156 @@
+// The 'waves' command: what's in each wave, for balancing.
+
+use std::collections::BTreeMap;
+
+use ludum_dare_37::fiends;
+use ludum_dare_37::model::*;
+use ludum_dare_37::rng::GameRng;
+
+use options::Format;
+
+// What's in a wave, added up.
+struct WaveTotals {
+    wave: usize,
+    // "big", "boss" or "none".
+    boss: &'static str,
+    fiends: usize,
+    health: usize,
+    effective_health: usize,
+    damage: usize,
+    armour: usize,
+    value: usize,
+    // How many fiends there are of each of 'FIEND_ARCHETYPES'.
+    archetypes: [usize; 4],
+    // How many of each fiend, by name and form.
+    names: BTreeMap<(FiendName, FiendForm), usize>,
+}
+
+impl WaveTotals {
+    fn new(wave: usize, difficulty: Difficulty, the_fiends: &[FiendInfo]) -> WaveTotals {
+        let boss = if difficulty.is_big_boss_wave(wave) {
+            "big"
+        } else if difficulty.is_boss_wave(wave) {
+            "boss"
+        } else {
+            "none"
+        };
+        let mut totals = WaveTotals {
+            wave,
+            boss,
+            fiends: the_fiends.len(),
+            health: 0,
+            effective_health: 0,
+            damage: 0,
+            armour: 0,
+            value: 0,
+            archetypes: [0; 4],
+            names: BTreeMap::new(),
+        };
+        for fiend in the_fiends {
+            totals.health += fiend.max_health;
+            totals.effective_health += fiend.effective_health();
+            totals.damage += fiend.damage_factor;
+            totals.armour += fiend.armour_factor;
+            totals.value += fiend.value;
+            let i = FIEND_ARCHETYPES.iter().position(|&a| a == fiend.archetype).unwrap();
+            totals.archetypes[i] += 1;
+            *totals.names.entry((fiend.name, fiend.form)).or_insert(0) += 1;
+        }
+        totals
+    }
+}
+
+/// Print what's in waves 'first' to 'last' of a game with the given
+/// seed and difficulty.
+pub fn waves(seed: u64, difficulty: Difficulty, (first, last): (usize, usize), format: Format) {
+    // Waves are generated one after another from the same rng, so the
+    // earlier ones have to be made even if they aren't shown.
+    let mut rng = GameRng::new(seed);
+    let totals = (1..last + 1)
+        .map(|wave| {
+            WaveTotals::new(wave, difficulty, &fiends::make_wave(wave, difficulty, &mut rng))
+        })
+        .skip(first - 1);
+    match format {
+        Format::Text => {
+            match difficulty {
+                Difficulty::Normal => println!("Seed {}", seed),
+                _ => println!("Seed {} ({})", seed, difficulty.name()),
+            }
+            totals.for_each(|t| print_text(&t));
+        }
+        Format::Csv => {
+            let archetypes = FIEND_ARCHETYPES.iter().map(|a| a.name()).collect::<Vec<_>>();
+            println!("wave,boss,fiends,hp,effective_hp,damage,armour,value,{}",
+                     archetypes.join(","));
+            totals.for_each(|t| print_csv(&t));
+        }
+        Format::Json => {
+            println!("[");
+            for (i, t) in totals.enumerate() {
+                if i > 0 {
+                    println!(",");
+                }
+                print_json(&t);
+            }
+            println!("\n]");
+        }
+    }
+}
+
+fn print_text(totals: &WaveTotals) {
+    let mut names = "".to_string();
+    let mut i = 0;
+    for (&(name, form), how_many) in &totals.names {
+        i += 1;
+        let form = match form {
+            FiendForm::Walking => String::new(),
+            form => format!(" ({})", form.name()),
+        };
+        names = format!("{} {}x {}{}{}",
+                        names,
+                        how_many,
+                        name,
+                        form,
+                        if i == totals.names.len() { "" } else { "," });
+    }
+    let tag = match totals.boss {
+        "big" => " [BIG BOSS]",
+        "boss" => " [BOSS]",
+        _ => "",
+    };
+    println!("Wave {}:{}{} ({} HP)", totals.wave, names, tag, totals.effective_health);
+}
+
+fn print_csv(totals: &WaveTotals) {
+    let archetypes = totals.archetypes.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    println!("{},{},{},{},{},{},{},{},{}",
+             totals.wave,
+             totals.boss,
+             totals.fiends,
+             totals.health,
+             totals.effective_health,
+             totals.damage,
+             totals.armour,
+             totals.value,
+             archetypes.join(","));
+}
+
+fn print_json(totals: &WaveTotals) {
+    let archetypes = FIEND_ARCHETYPES.iter()
+        .zip(totals.archetypes.iter())
+        .map(|(a, n)| format!("\"{}\": {}", a.name(), n))
+        .collect::<Vec<_>>();
+    print!("  {{\"wave\": {}, \"boss\": \"{}\", \"fiends\": {}, \"hp\": {}, \
+            \"effective_hp\": {}, \"damage\": {}, \"armour\": {}, \"value\": {}, \
+            \"archetypes\": {{{}}}}}",
+           totals.wave,
+           totals.boss,
+           totals.fiends,
+           totals.health,
+           totals.effective_health,
+           totals.damage,
+           totals.armour,
+           totals.value,
+           archetypes.join(", "));
+}
@@ -0,0 +1,147 @@
+//! Fiends and arrows running into each other, and how armour helps.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::Game;
+use ludum_dare_37::model::*;
+
+const CORRIDOR: &str = "
+##########
+-  @    Y#
+##########
+";
+
+// A fight in a corridor, with a fiend heading for the Thing and an
+// arrow in the space in front of it, flying away from it.
+fn fight(health: usize, faction: Faction) -> Game {
+    let mut game = Game::new(common::settings(CORRIDOR));
+    common::add_fiend(&mut game, (5, 1), common::rat(health));
+
+    let arrow = ArrowInfo::new((6, 1), (8, 1), 1, 50, faction);
+    game.world_data.mobiles[1][6] = Some(Arrow { info: arrow });
+    game.world_data.arrows.insert((6, 1));
+
+    common::start_fight(&mut game);
+    game
+}
+
+fn fiend_health(game: &Game) -> usize {
+    match game.world_data.mobiles[1][5] {
+        Some(Fiend { info }) => info.health,
+        mob => panic!("The fiend has moved (got {:?})", mob),
+    }
+}
+
+#[test]
+fn fiends_walking_into_arrows_are_hit() {
+    let mut game = fight(100, Faction::Defenders);
+    game.tick();
+    assert_eq!(fiend_health(&game), 50);
+    assert!(game.world_data.arrows.is_empty());
+    assert_eq!(game.world_data.log[0], "rat is shot for 50 damage! (50 / 100)");
+}
+
+#[test]
+fn fiends_killed_by_walking_into_arrows_are_paid_for() {
+    let mut game = fight(10, Faction::Defenders);
+    let cash = game.world_data.cash;
+    game.tick();
+    assert!(game.world_data.fiends.is_empty());
+    assert!(game.world_data.arrows.is_empty());
+    assert_eq!(game.world_data.cash, cash + 10);
+    assert_eq!(game.world_data.log[0], "rat is shot for 50 damage! (dead!)");
+}
+
+#[test]
+fn fiends_wait_for_their_own_arrows() {
+    let mut game = fight(100, Faction::Fiends);
+    game.tick();
+    assert_eq!(fiend_health(&game), 100);
+    assert!(game.world_data.arrows.contains(&(7, 1)));
+}
+
+fn armour_fiend(game: &mut Game, armour_factor: usize) {
+    if let Some(Fiend { ref mut info }) = game.world_data.mobiles[1][5] {
+        info.armour_factor = armour_factor;
+    }
+}
+
+#[test]
+fn armour_blunts_arrows() {
+    let mut game = fight(100, Faction::Defenders);
+    armour_fiend(&mut game, 100);
+    game.tick();
+    assert_eq!(fiend_health(&game), 75);
+}
+
+#[test]
+fn arrows_always_do_some_damage() {
+    let mut game = fight(100, Faction::Defenders);
+    armour_fiend(&mut game, 1_000_000);
+    game.tick();
+    assert_eq!(fiend_health(&game), 99);
+}
+
+#[test]
+fn armour_blunts_the_players_hits() {
+    let mut game = Game::new(common::settings(CORRIDOR));
+    common::add_fiend(&mut game, (4, 1), FiendInfo { armour_factor: 100, ..common::rat(100) });
+    game.world_data.player_info.damage_factor = 50;
+    common::start_fight(&mut game);
+    game.command(Command::Move(Dir::E));
+    assert_eq!(game.world_data.log[0], "rat is hit for 25 damage! (75 / 100)");
+}
+
+// A long corridor, for archers to keep their distance in.
+const RANGE: &str = "
+thing 100
+
+################
+-@            Y#
+################
+";
+
+fn archer(player_target_distance: usize, goal_target_distance: usize) -> FiendInfo {
+    FiendInfo {
+        archetype: Archetype::Archer,
+        player_target_distance,
+        goal_target_distance,
+        ..common::rat(100)
+    }
+}
+
+// Where the only fiend is.
+fn fiend_xy(game: &Game) -> (usize, usize) {
+    *game.world_data.fiends.iter().next().expect("The fiend has gone")
+}
+
+#[test]
+fn archers_shoot_the_thing_from_a_distance() {
+    let mut game = Game::new(common::settings(RANGE));
+    common::add_fiend(&mut game, (2, 1), archer(0, 1000));
+    common::start_fight(&mut game);
+    for _ in 0..20 {
+        game.tick();
+    }
+    // The Thing is at (14, 1).
+    assert_eq!(fiend_xy(&game), (14 - Archetype::Archer.range(), 1));
+    match game.world_data.statics[1][14] {
+        Some(Goal { health, .. }) => assert!(health < 100),
+        sta => panic!("The Thing is missing (got {:?})", sta),
+    }
+}
+
+#[test]
+fn archers_shoot_the_player_from_a_distance() {
+    let mut game = Game::new(common::settings(RANGE));
+    common::add_fiend(&mut game, (12, 1), archer(1000, 0));
+    common::start_fight(&mut game);
+    for _ in 0..20 {
+        game.tick();
+    }
+    // The player is at (1, 1).
+    assert_eq!(fiend_xy(&game), (1 + Archetype::Archer.range(), 1));
+    assert!(game.world_data.player_info.health < 100);
+}
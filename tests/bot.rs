@@ -0,0 +1,82 @@
+//! The bot finding its way around the menus and the room.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::{bot, Game, Settings};
+use ludum_dare_37::model::*;
+
+const ROOM: &str = "
+#####-#####
+#         #
+#         #
+#    Y    #
+#         #
+#       @ #
+#####-#####
+";
+
+fn game(cash: usize) -> Game {
+    let mut settings = common::settings(ROOM);
+    settings.starting_cash = cash;
+    Game::new(settings)
+}
+
+// Let the bot give commands until it starts a fight.
+fn construct(game: &mut Game) {
+    for _ in 0..1000 {
+        if let Fight { .. } = game.state {
+            return;
+        }
+        let command = bot::command(game).expect("The bot gave up");
+        game.command(command);
+    }
+    panic!("The bot never started the fight");
+}
+
+#[test]
+fn builds_through_the_menus() {
+    let mut game = game(100);
+    construct(&mut game);
+
+    let world_data = &game.world_data;
+    assert_eq!(world_data.turrets.len(), 1);
+    assert_eq!(world_data.obstacles.len(), 4);
+    assert_eq!(world_data.cash, 0);
+    // The Thing is walled in by obstacles.
+    for &(x, y) in &[(5, 2), (6, 3), (5, 4), (4, 3)] {
+        assert!(world_data.obstacles.contains(&(x, y)), "No obstacle at ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn saves_up_for_what_it_wants() {
+    let mut game = game(50);
+    construct(&mut game);
+
+    let world_data = &game.world_data;
+    assert!(world_data.turrets.is_empty());
+    assert!(world_data.obstacles.is_empty());
+    assert_eq!(world_data.cash, 50);
+}
+
+#[test]
+fn hits_back() {
+    let mut game = game(0);
+    construct(&mut game);
+
+    // One fiend next to the player, and another closer to the Thing.
+    for &xy in &[(9, 5), (5, 1)] {
+        common::add_fiend(&mut game, xy, common::rat(1000));
+    }
+
+    assert_eq!(bot::command(&game), Some(Command::Move(Dir::E)));
+}
+
+#[test]
+fn games_end() {
+    let outcome = bot::play(Settings::new(37), 5);
+    assert!(outcome.wave >= 1 && outcome.wave <= 5);
+    assert_eq!(outcome, bot::play(Settings::new(37), 5));
+}
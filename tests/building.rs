@@ -0,0 +1,57 @@
+//! Building turrets and obstacles, and paying for them.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::Game;
+use ludum_dare_37::model::*;
+
+const ROOM: &str = "
+#####-#####
+#         #
+#    Y    #
+#       @ #
+#####-#####
+";
+
+const TURRET: Buildable = Buildable::Turret(TurretForm::Basic);
+
+// A game in the construct phase, with the given cash to spend.
+fn game(cash: usize) -> Game {
+    let mut settings = common::settings(ROOM);
+    settings.starting_cash = cash;
+    let mut game = Game::new(settings);
+    game.command(Command::Select);
+    game
+}
+
+#[test]
+fn building_costs_cash() {
+    let mut game = game(100);
+    game.command(Command::Build(TURRET, (2, 1)));
+    game.command(Command::Build(Buildable::Obstacle, (3, 1)));
+    assert!(game.world_data.turrets.contains(&(2, 1)));
+    assert!(game.world_data.obstacles.contains(&(3, 1)));
+    assert_eq!(game.world_data.cash, 100 - TURRET.cost() - Buildable::Obstacle.cost());
+}
+
+#[test]
+fn building_without_the_cash_is_refused() {
+    let cash = TURRET.cost() - 1;
+    let mut game = game(cash);
+    game.command(Command::Build(TURRET, (2, 1)));
+    assert!(game.world_data.turrets.is_empty());
+    assert_eq!(game.world_data.cash, cash);
+    assert_eq!(game.world_data.log[0],
+               format!("You can't afford that! It costs {}.", TURRET.cost()));
+
+    // The build menu doesn't let it be picked up to place, either.
+    game.command(Command::Select);
+    game.command(Command::Select);
+    assert!(game.state ==
+            Construct {
+                menu: Menu::Build,
+                menu_index: 0,
+            });
+}
@@ -0,0 +1,56 @@
+//! Setting up games for the tests.
+
+// Not every test uses all of these.
+#![allow(dead_code)]
+
+use ludum_dare_37::{Game, Settings};
+use ludum_dare_37::map::Map;
+use ludum_dare_37::model::*;
+
+/// The settings for a game on the given map.
+pub fn settings(map: &str) -> Settings {
+    let mut settings = Settings::new(37);
+    settings.set_map(Map::read(map.as_bytes()).expect("Bad test map"));
+    settings
+}
+
+/// Skip straight to the fight, with no more fiends to come than are
+/// already in the room.
+pub fn start_fight(game: &mut Game) {
+    game.state = GameState::Fight {
+        to_spawn: Vec::new(),
+        player_moved: false,
+    };
+}
+
+/// A walking rat with the given health, which only cares about the
+/// Thing.
+pub fn rat(health: usize) -> FiendInfo {
+    FiendInfo {
+        glyph: Glyph::plain('r'),
+        name: FiendName {
+            prefix: "",
+            name: "rat",
+            suffix: None,
+        },
+        form: FiendForm::Walking,
+        archetype: Archetype::Raider,
+        health,
+        max_health: health,
+        damage_factor: 1,
+        armour_factor: 0,
+        player_target_distance: 0,
+        goal_target_distance: 1000,
+        turret_target_distance: 0,
+        obstacle_target_distance: 0,
+        value: 10,
+        slowed: 0,
+        reload: 0,
+        stuck: 0,
+    }
+}
+
+pub fn add_fiend(game: &mut Game, (x, y): (usize, usize), info: FiendInfo) {
+    game.world_data.mobiles[y][x] = Some(Fiend { info });
+    game.world_data.fiends.insert((x, y));
+}
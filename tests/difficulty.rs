@@ -0,0 +1,134 @@
+//! What the difficulty changes.
+
+extern crate ludum_dare_37;
+
+use ludum_dare_37::{Game, Settings};
+use ludum_dare_37::fiends::{is_boss, make_wave};
+use ludum_dare_37::map::Map;
+use ludum_dare_37::model::*;
+use ludum_dare_37::replay::{Recorder, Replay};
+use ludum_dare_37::rng::GameRng;
+
+const ROOM: &str = "
+thing 20
+
+#####-#####
+#         #
+#    Y    #
+#         #
+#         #
+#         #
+#         #
+#@        #
+#####-#####
+";
+
+fn settings(difficulty: Difficulty) -> Settings {
+    let mut settings = Settings::new(37);
+    settings.set_map(Map::read(ROOM.as_bytes()).expect("Bad test map"));
+    settings.difficulty = difficulty;
+    settings
+}
+
+fn thing_health(game: &Game) -> usize {
+    match game.world_data.statics[2][5] {
+        Some(Goal { max_health, .. }) => max_health,
+        sta => panic!("The Thing is missing (got {:?})", sta),
+    }
+}
+
+// How many points went on the fiends in the first few waves.
+fn points(difficulty: Difficulty) -> usize {
+    let mut rng = GameRng::new(37);
+    (1..21).flat_map(|wave| make_wave(wave, difficulty, &mut rng)).map(|f| f.value).sum()
+}
+
+#[test]
+fn harder_games_have_stronger_waves() {
+    let points = DIFFICULTIES.iter().map(|&d| points(d)).collect::<Vec<_>>();
+    assert!(points.windows(2).all(|pair| pair[0] < pair[1]),
+            "Points by difficulty: {:?}",
+            points);
+}
+
+#[test]
+fn harder_games_have_more_bosses() {
+    let boss_waves = |difficulty| {
+        let mut rng = GameRng::new(37);
+        (1..13)
+            .filter(|&wave| {
+                let fiends = make_wave(wave, difficulty, &mut rng);
+                fiends.len() == 1 && is_boss(&fiends[0].name)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(boss_waves(Difficulty::Easy), [6, 12]);
+    assert_eq!(boss_waves(Difficulty::Normal), [5, 10]);
+    assert_eq!(boss_waves(Difficulty::Hard), [4, 8, 12]);
+    assert_eq!(boss_waves(Difficulty::Nightmare), [3, 6, 9, 12]);
+}
+
+#[test]
+fn big_bosses_are_announced_by_name() {
+    // The sixth wave is a big boss in a nightmare.
+    let mut game = Game::new(settings(Difficulty::Nightmare));
+    game.command(Command::Select);
+    game.world_data.wave = 5;
+    game.command(Command::Continue);
+    while !game.world_data.log[0].ends_with("appears!") {
+        game.tick();
+    }
+    assert!(!game.world_data.log[0].starts_with("A "), "{}", game.world_data.log[0]);
+}
+
+#[test]
+fn harder_games_have_weaker_things() {
+    let health = DIFFICULTIES.iter()
+        .map(|&d| thing_health(&Game::new(settings(d))))
+        .collect::<Vec<_>>();
+    assert_eq!(health, [30, 20, 15, 10]);
+}
+
+#[test]
+fn the_difficulty_is_picked_on_the_title_screen() {
+    let mut game = Game::new(settings(Difficulty::Normal));
+    game.command(Command::Move(Dir::E));
+    game.command(Command::Move(Dir::E));
+    game.command(Command::Move(Dir::E));
+    assert_eq!(game.world_data.difficulty, Difficulty::Nightmare);
+    assert_eq!(game.settings.difficulty, Difficulty::Nightmare);
+    assert_eq!(thing_health(&game), 10);
+
+    game.command(Command::Move(Dir::W));
+    assert_eq!(game.world_data.difficulty, Difficulty::Hard);
+    assert_eq!(thing_health(&game), 15);
+
+    // Up and down start the game, like any other key.
+    game.command(Command::Move(Dir::N));
+    assert!(game.state != Startup);
+
+    // Once the game's started, it's too late.
+    game.command(Command::Move(Dir::W));
+    assert_eq!(game.world_data.difficulty, Difficulty::Hard);
+    assert_eq!(thing_health(&game), 15);
+}
+
+#[test]
+fn saves_keep_the_difficulty() {
+    let mut game = Game::new(settings(Difficulty::Easy));
+    game.command(Command::Select);
+    let mut saved = Vec::new();
+    game.save(&mut saved).unwrap();
+    let loaded = Game::load(saved.as_slice()).unwrap();
+    assert_eq!(loaded.settings.difficulty, Difficulty::Easy);
+    assert_eq!(loaded.world_data.difficulty, Difficulty::Easy);
+    assert_eq!(thing_health(&loaded), 30);
+}
+
+#[test]
+fn replays_keep_the_difficulty() {
+    let mut recorded = Vec::new();
+    Recorder::new(&mut recorded, &settings(Difficulty::Hard)).unwrap();
+    let replay = Replay::read(recorded.as_slice()).unwrap();
+    assert_eq!(replay.settings, settings(Difficulty::Hard));
+}
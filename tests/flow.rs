@@ -0,0 +1,163 @@
+//! The routes fiends take to what they're after.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::{FlowFields, Game};
+use ludum_dare_37::model::*;
+
+// The Thing is at (7, 1), with an obstacle in the way along the top.
+const OBSTACLE: &str = "
+thing 10
+
+#########
+|   =  Y#
+#       #
+#@#######
+#########
+";
+
+// The Thing is at (7, 1), with a wall in the way along the top.
+const WALL: &str = "
+thing 10
+
+#########
+|  #   Y#
+#  #    #
+#  #    #
+#       #
+#@#######
+#########
+";
+
+const THING: (usize, usize) = (7, 1);
+
+fn fiend(form: FiendForm, damage_factor: usize) -> FiendInfo {
+    FiendInfo { form, damage_factor, ..common::rat(100) }
+}
+
+fn distance(map: &str, from: (usize, usize), info: FiendInfo) -> Option<usize> {
+    let game = Game::new(common::settings(map));
+    FlowFields::new().distance(&game.world_data, from, THING, info)
+}
+
+fn next_step(map: &str, from: (usize, usize), info: FiendInfo) -> Option<(usize, usize)> {
+    let game = Game::new(common::settings(map));
+    FlowFields::new().next_step(&game.world_data, from, THING, info)
+}
+
+// The spaces a fiend passes through on its way to the Thing, ending
+// with the Thing itself.
+fn route(map: &str, from: (usize, usize), info: FiendInfo) -> Vec<(usize, usize)> {
+    let game = Game::new(common::settings(map));
+    let mut paths = FlowFields::new();
+    let mut route = vec![];
+    let mut xy = from;
+    while xy != THING {
+        xy = paths.next_step(&game.world_data, xy, THING, info).expect("No way to the Thing");
+        assert!(route.len() < 100, "Going round in circles: {:?}", route);
+        route.push(xy);
+    }
+    route
+}
+
+// Every move costs one, plus the time spent breaking into the space
+// moved into. The Thing takes 10 / damage to break.
+
+#[test]
+fn walking_fiends_go_round_obstacles() {
+    let walker = fiend(FiendForm::Walking, 1);
+    // Through row 2: 7 moves and the Thing.
+    assert_eq!(distance(OBSTACLE, (1, 1), walker), Some(7 + 11));
+    assert_eq!(route(OBSTACLE, (1, 1), walker).len(), 8);
+    assert_eq!(next_step(OBSTACLE, (3, 1), walker), Some((3, 2)));
+    assert_eq!(distance(OBSTACLE, (3, 1), walker), Some(5 + 11));
+}
+
+#[test]
+fn flying_fiends_go_over_obstacles() {
+    let flier = fiend(FiendForm::Flying, 1);
+    assert_eq!(route(OBSTACLE, (1, 1), flier), [(2, 1), (3, 1), (4, 1), (5, 1), (6, 1), THING]);
+    assert_eq!(distance(OBSTACLE, (1, 1), flier), Some(5 + 11));
+}
+
+#[test]
+fn leaping_fiends_jump_over_obstacles() {
+    let leaper = fiend(FiendForm::Leaping, 1);
+    assert_eq!(route(OBSTACLE, (1, 1), leaper), [(2, 1), (3, 1), (5, 1), (6, 1), THING]);
+    // Two moves, a leap which costs two, another move and the Thing.
+    assert_eq!(distance(OBSTACLE, (1, 1), leaper), Some(2 + 2 + 1 + 11));
+}
+
+#[test]
+fn walking_fiends_go_round_walls() {
+    let walker = fiend(FiendForm::Walking, 1);
+    // Down to row 4, across and back up: 10 moves and the Thing.
+    assert_eq!(distance(WALL, (2, 1), walker), Some(10 + 11));
+    assert_eq!(route(WALL, (2, 1), walker).len(), 11);
+    assert_eq!(next_step(WALL, (2, 1), walker), Some((2, 2)));
+}
+
+#[test]
+fn flying_fiends_dont_go_over_walls() {
+    let flier = fiend(FiendForm::Flying, 1);
+    assert_eq!(distance(WALL, (2, 1), flier), Some(10 + 11));
+}
+
+#[test]
+fn tunnelling_fiends_go_through_walls() {
+    let tunneller = fiend(FiendForm::Tunnelling, 1);
+    assert_eq!(route(WALL, (2, 1), tunneller), [(3, 1), (4, 1), (5, 1), (6, 1), THING]);
+    // The wall takes one turn to dig through.
+    assert_eq!(distance(WALL, (2, 1), tunneller), Some(2 + 3 + 11));
+}
+
+#[test]
+fn strong_fiends_go_through_obstacles() {
+    // The obstacle takes 300 / 256 = 1 turn to break, and the Thing
+    // none at all.
+    let brute = fiend(FiendForm::Walking, 256);
+    assert_eq!(next_step(OBSTACLE, (3, 1), brute), Some((4, 1)));
+    assert_eq!(distance(OBSTACLE, (3, 1), brute), Some(2 + 1 + 1 + 1));
+}
+
+#[test]
+fn damage_is_counted_in_bands() {
+    let next_to_thing = |damage_factor| {
+        distance(OBSTACLE, (6, 1), fiend(FiendForm::Walking, damage_factor))
+    };
+    assert_eq!(next_to_thing(0), Some(10 + 1));
+    assert_eq!(next_to_thing(1), Some(10 + 1));
+    assert_eq!(next_to_thing(2), Some(5 + 1));
+    assert_eq!(next_to_thing(3), Some(5 + 1));
+    assert_eq!(next_to_thing(4), Some(2 + 1));
+    assert_eq!(next_to_thing(7), Some(2 + 1));
+    assert_eq!(next_to_thing(8), Some(1 + 1));
+}
+
+#[test]
+fn fields_keep_up_with_the_room() {
+    let walker = fiend(FiendForm::Walking, 1);
+    let mut game = Game::new(common::settings(OBSTACLE));
+    let mut paths = FlowFields::new();
+    assert_eq!(paths.next_step(&game.world_data, (3, 1), THING, walker), Some((3, 2)));
+
+    // Worn down, the obstacle is quicker to go through than round.
+    game.world_data.statics[1][4] = Some(Obstacle {
+        health: 1,
+        max_health: 300,
+    });
+    paths.update(&game.world_data);
+    assert_eq!(paths.next_step(&game.world_data, (3, 1), THING, walker), Some((4, 1)));
+    // The obstacle now costs two, then two moves and the Thing.
+    assert_eq!(paths.distance(&game.world_data, (3, 1), THING, walker), Some(2 + 2 + 11));
+
+    // Building it back up sends fiends the long way round again.
+    game.world_data.statics[1][4] = Some(Obstacle {
+        health: 300,
+        max_health: 300,
+    });
+    paths.update(&game.world_data);
+    assert_eq!(paths.distance(&game.world_data, (3, 1), THING, walker), Some(5 + 11));
+}
@@ -0,0 +1,175 @@
+//! Reading rooms from map files, and making rooms of any size.
+
+extern crate ludum_dare_37;
+
+use ludum_dare_37::{Game, Settings, MIN_ROOM_SIZE};
+use ludum_dare_37::map::{Map, MapError, DEFAULT_THING_HEALTH};
+use ludum_dare_37::model::*;
+
+const ROOM: &str = "cash 150
+thing 20
+
+####---####
+#         #
+|  O   =  |
+#    Y    #
+#  @      #
+####---####
+";
+
+// Where and why a map couldn't be read.
+fn parse_error(map: &str) -> (usize, usize, String) {
+    match Map::read(map.as_bytes()) {
+        Err(MapError::Parse { line, col, msg }) => (line, col, msg),
+        Err(err) => panic!("Wrong error: {}", err),
+        Ok(_) => panic!("The map was read:\n{}", map),
+    }
+}
+
+fn invalid(map: &str) -> String {
+    match Map::read(map.as_bytes()) {
+        Err(MapError::Invalid(msg)) => msg,
+        Err(err) => panic!("Wrong error: {}", err),
+        Ok(_) => panic!("The map was read:\n{}", map),
+    }
+}
+
+#[test]
+fn maps_are_read() {
+    let map = Map::read(ROOM.as_bytes()).unwrap();
+    assert_eq!(map.starting_cash, Some(150));
+    assert_eq!(map.thing_health, 20);
+    assert_eq!((map.width(), map.height()), (11, 6));
+    assert_eq!(Map::read(map.to_string().as_bytes()).unwrap(), map);
+}
+
+#[test]
+fn settings_are_optional() {
+    let map = Map::read("###\n|Y#\n#@#\n###".as_bytes()).unwrap();
+    assert_eq!(map.starting_cash, None);
+    assert_eq!(map.thing_health, DEFAULT_THING_HEALTH);
+}
+
+#[test]
+fn blank_lines_after_the_room_are_ignored() {
+    let map = Map::read("#####\n#Y @#\n|   #\n#####\n\n\n".as_bytes()).unwrap();
+    assert_eq!((map.width(), map.height()), (5, 4));
+}
+
+#[test]
+fn rooms_are_walled_in() {
+    let edge = |ch: char| format!("the edge of the room must be wall or gate, not {:?}", ch);
+    assert_eq!(parse_error("#####\n#   #\n|Y  @\n#   #\n#####"), (3, 5, edge('@')));
+    // Short rows are filled out with floor, which leaves a hole.
+    assert_eq!(parse_error("#####\n#  #\n|Y @#\n#####"), (2, 5, edge(' ')));
+    assert_eq!(parse_error("#####\n#Y @#\n#\n|   #\n#####"), (3, 5, edge(' ')));
+    assert_eq!(parse_error("## ##\n#   #\n|Y @#\n#####"), (1, 3, edge(' ')));
+    assert_eq!(parse_error("#####\n#   #\nY  @|\n#####"), (3, 1, edge('Y')));
+    assert_eq!(parse_error("#####\n#Y @|\n##=##"), (3, 3, edge('=')));
+    assert_eq!(parse_error("#####\n#Y @|\n##O##"), (3, 3, edge('O')));
+}
+
+#[test]
+fn bad_settings_are_rejected() {
+    let room = "\n###\n|Y#\n#@#\n###";
+    let error = |setting: &str| parse_error(&format!("{}{}", setting, room));
+    assert_eq!(error("cash lots"), (1, 0, "bad value for cash: lots".to_string()));
+    assert_eq!(error("cash 1 2"), (1, 0, "bad setting: cash 1 2".to_string()));
+    assert_eq!(error("fiends 10"), (1, 0, "unknown setting: fiends".to_string()));
+    assert_eq!(error("thing 0"), (1, 0, "the Thing can't start out destroyed".to_string()));
+}
+
+#[test]
+fn bad_characters_are_rejected() {
+    assert_eq!(parse_error("#####\n#Y x#\n|@  #\n#####"),
+               (2, 4, "unknown map character 'x'".to_string()));
+    assert_eq!(parse_error("#####\n#Y @#\n|Y  #\n#####"),
+               (3, 2, "there's already a 'Y' at line 2, column 2".to_string()));
+    assert_eq!(parse_error("#####\n#Y @#\n|@  #\n#####"),
+               (3, 2, "there's already a '@' at line 2, column 4".to_string()));
+}
+
+#[test]
+fn incomplete_rooms_are_rejected() {
+    assert_eq!(invalid("##\n|Y\n#@"), "the room must be at least 3x3");
+    assert_eq!(invalid("###\n|Y|"), "the room must be at least 3x3");
+    assert_eq!(invalid("cash 10\n"), "the room must be at least 3x3");
+    assert_eq!(invalid("###\n|@#\n###"), "the map has no Thing (Y)");
+    assert_eq!(invalid("###\n|Y#\n###"), "the map has no player start (@)");
+    assert_eq!(invalid("####\n#Y@#\n####"),
+               "the map has no gates (- or |) for fiends to come in through");
+}
+
+#[test]
+fn the_thing_must_be_reachable() {
+    let cut_off = "fiends can't get from any gate to the Thing (Y) without going through walls";
+    assert_eq!(invalid("#####\n#Y#@#\n#####\n|   #\n#####"), cut_off);
+    assert_eq!(invalid("##-##\n#####\n#Y @#\n#####"), cut_off);
+    // Anything but a wall can be broken through.
+    assert!(Map::read("#####\n#Y=@#\n###O#\n|   #\n#####".as_bytes()).is_ok());
+}
+
+#[test]
+fn missing_files_are_reported() {
+    match Map::load_file("maps/no such map.txt") {
+        Err(MapError::Io(_)) => (),
+        Err(err) => panic!("Wrong error: {}", err),
+        Ok(_) => panic!("A map which isn't there was read"),
+    }
+}
+
+#[test]
+fn errors_say_where_they_are() {
+    let error = |line, col| MapError::Parse { line, col, msg: "oops".to_string() }.to_string();
+    assert_eq!(error(3, 0), "line 3: oops");
+    assert_eq!(error(3, 5), "line 3, column 5: oops");
+}
+
+fn room(width: usize, height: usize) -> Game {
+    let mut settings = Settings::new(37);
+    settings.width = width;
+    settings.height = height;
+    Game::new(settings)
+}
+
+#[test]
+fn rooms_are_made_in_any_size() {
+    for &(width, height) in &[(MIN_ROOM_SIZE, MIN_ROOM_SIZE), (63, 31), (100, 12), (15, 50)] {
+        let game = room(width, height);
+        let world_data = &game.world_data;
+        assert_eq!((world_data.width(), world_data.height()), (width, height));
+        for (y, row) in world_data.statics.rows().enumerate() {
+            for (x, sta) in row.iter().enumerate() {
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    assert!(matches!(sta, Some(Wall) | Some(Gate)),
+                            "({}, {}) is open in a {}x{} room",
+                            x,
+                            y,
+                            width,
+                            height);
+                }
+            }
+        }
+        assert_eq!(world_data.goal_location, (width / 2, height / 2));
+        assert_eq!(world_data.gates.len(), 28);
+        // They're good enough to save as maps.
+        let map = Map::room(width, height);
+        assert_eq!(Map::read(map.to_string().as_bytes()).unwrap(), map);
+    }
+}
+
+#[test]
+fn waves_play_out_in_small_rooms() {
+    let mut game = room(MIN_ROOM_SIZE, MIN_ROOM_SIZE);
+    game.command(Command::Select);
+    game.command(Command::Continue);
+    let mut fiends = 0;
+    for _ in 0..1000 {
+        if let Fight { .. } = game.state {
+            game.tick();
+            fiends = fiends.max(game.world_data.fiends.len());
+        }
+    }
+    assert!(fiends > 0, "No fiends came in");
+    assert!(!matches!(game.state, Fight { .. }), "The wave never ended");
+}
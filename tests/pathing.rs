@@ -0,0 +1,255 @@
+//! Fiends which can't get where they want to go.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::Game;
+use ludum_dare_37::model::*;
+
+// A fight on the given map, with a fiend of the given form at each
+// location. The fiends only care about the Thing, and are tough enough
+// to survive any turrets for the length of a test.
+//
+// Maps with no way from the gates to the Thing can't be read, so walls
+// which cut it off are marked 'X', and put up once the game's started.
+fn fight(map: &str, form: FiendForm, fiends: &[(usize, usize)]) -> Game {
+    let mut game = Game::new(common::settings(&map.replace('X', " ")));
+    let rows = map.lines().skip_while(|line| line.trim().is_empty());
+    for (y, row) in rows.enumerate() {
+        for (x, ch) in row.chars().enumerate() {
+            if ch == 'X' {
+                game.world_data.statics[y][x] = Some(Wall);
+            }
+        }
+    }
+    for &xy in fiends {
+        common::add_fiend(&mut game, xy, fiend(form));
+    }
+    common::start_fight(&mut game);
+    game
+}
+
+fn fiend(form: FiendForm) -> FiendInfo {
+    FiendInfo { form, ..common::rat(1_000_000) }
+}
+
+fn ticks(game: &mut Game, n: usize) {
+    for _ in 0..n {
+        game.tick();
+    }
+}
+
+// Whether the wave is over within the given number of ticks.
+fn wave_ends(game: &mut Game, n: usize) -> bool {
+    for _ in 0..n {
+        game.tick();
+        if let GameState::Construct { .. } = game.state {
+            return true;
+        }
+    }
+    false
+}
+
+fn thing_health(game: &Game) -> usize {
+    let (x, y) = game.world_data.goal_location;
+    match game.world_data.statics[y][x] {
+        Some(Goal { health, .. }) => health,
+        sta => panic!("The Thing is missing (got {:?})", sta),
+    }
+}
+
+fn turret_health(game: &Game, (x, y): (usize, usize)) -> usize {
+    match game.world_data.statics[y][x] {
+        Some(Turret { info }) => info.health,
+        sta => panic!("The turret is missing (got {:?})", sta),
+    }
+}
+
+const ENCLOSED_GATE: &str = "
+#####-#####
+#   XXX   #
+#         #
+#    Y    #
+#  @      #
+###########
+";
+
+#[test]
+fn walled_in_fiends_wait_then_leave() {
+    let mut game = fight(ENCLOSED_GATE, FiendForm::Walking, &[(5, 0)]);
+    ticks(&mut game, 20);
+    assert!(game.world_data.fiends.contains(&(5, 0)));
+    assert!(wave_ends(&mut game, 100));
+    assert_eq!(thing_health(&game), 10);
+}
+
+#[test]
+fn walled_in_flying_fiends_wait_then_leave() {
+    let mut game = fight(ENCLOSED_GATE, FiendForm::Flying, &[(5, 0)]);
+    ticks(&mut game, 20);
+    assert!(game.world_data.fiends.contains(&(5, 0)));
+    assert!(wave_ends(&mut game, 100));
+    assert_eq!(thing_health(&game), 10);
+}
+
+#[test]
+fn walled_in_fiends_tunnel_out() {
+    let mut game = fight(ENCLOSED_GATE, FiendForm::Tunnelling, &[(5, 0)]);
+    ticks(&mut game, 20);
+    assert!(thing_health(&game) < 10);
+}
+
+#[test]
+fn walled_in_crowds_wait_then_leave() {
+    let map = "
+#---#
+#XXX#
+#   #
+# Y #
+#  @#
+#####
+";
+    let mut game = fight(map, FiendForm::Walking, &[(1, 0), (2, 0), (3, 0)]);
+    ticks(&mut game, 20);
+    assert_eq!(game.world_data.fiends.len(), 3);
+    assert!(wave_ends(&mut game, 100));
+}
+
+const WALLED_OFF_THING: &str = "
+#####-#####
+#         #
+#   XXX   #
+#   XYX   #
+#   XXX   #
+# @       #
+###########
+";
+
+#[test]
+fn fiends_go_for_the_player_if_the_thing_is_walled_off() {
+    let mut game = fight(WALLED_OFF_THING, FiendForm::Walking, &[(5, 0)]);
+    ticks(&mut game, 30);
+    assert!(game.world_data.player_info.health < 100);
+    assert_eq!(thing_health(&game), 10);
+}
+
+const WALLED_OFF_THING_AND_PLAYER: &str = "
+#####-#####
+#         #
+#  XXXXX  #
+#  XY @X  #
+#  XXXXX  #
+#     O   #
+###########
+";
+
+#[test]
+fn fiends_go_for_turrets_if_everything_else_is_walled_off() {
+    let mut game = fight(WALLED_OFF_THING_AND_PLAYER, FiendForm::Walking, &[(5, 0)]);
+    let max_health = turret_health(&game, (6, 5));
+    ticks(&mut game, 30);
+    assert!(turret_health(&game, (6, 5)) < max_health);
+    assert_eq!(game.world_data.player_info.health, 100);
+}
+
+#[test]
+fn flying_fiends_attack_turrets_if_everything_else_is_walled_off() {
+    let mut game = fight(WALLED_OFF_THING_AND_PLAYER, FiendForm::Flying, &[(5, 0)]);
+    let max_health = turret_health(&game, (6, 5));
+    ticks(&mut game, 30);
+    assert!(turret_health(&game, (6, 5)) < max_health);
+}
+
+#[test]
+fn fiends_with_nothing_to_attack_wait_then_leave() {
+    let map = "
+#####-#####
+#         #
+#  XXXXX  #
+#  XY @X  #
+#  XXXXX  #
+#         #
+###########
+";
+    let mut game = fight(map, FiendForm::Walking, &[(5, 0)]);
+    ticks(&mut game, 30);
+    assert_eq!(game.world_data.fiends.len(), 1);
+    assert!(wave_ends(&mut game, 100));
+    assert_eq!(thing_health(&game), 10);
+    assert_eq!(game.world_data.player_info.health, 100);
+}
+
+#[test]
+fn fiends_break_through_obstacles_to_the_thing() {
+    let map = "
+#####-#####
+#         #
+#   #=#   #
+#   #Y#   #
+#   ###   #
+# @       #
+###########
+";
+    let mut game = fight(map, FiendForm::Walking, &[(5, 0)]);
+    ticks(&mut game, 60);
+    match game.world_data.statics[2][5] {
+        Some(Obstacle { health, max_health }) => assert!(health < max_health),
+        None => assert!(thing_health(&game) < 10),
+        sta => panic!("Expected an obstacle (got {:?})", sta),
+    }
+}
+
+#[test]
+fn fiends_going_opposite_ways_swap_places() {
+    let map = "
+#######-#######
+#@           Y#
+###############
+";
+    let mut game = fight(map, FiendForm::Walking, &[(7, 1), (8, 1)]);
+    let mut hunter = fiend(FiendForm::Walking);
+    hunter.player_target_distance = 1000;
+    hunter.goal_target_distance = 0;
+    game.world_data.mobiles[1][8] = Some(Fiend { info: hunter });
+    ticks(&mut game, 20);
+    assert!(game.world_data.player_info.health < 100);
+    assert!(thing_health(&game) < 10);
+}
+
+#[test]
+fn fiends_step_around_fiends_which_have_stopped() {
+    let map = "
+#################
+-     @        ##
+-              Y#
+#################
+";
+    // The archer stops to shoot the Thing, in the way of the other.
+    let mut game = fight(map, FiendForm::Walking, &[(9, 2), (10, 2)]);
+    let mut archer = fiend(FiendForm::Walking);
+    archer.archetype = Archetype::Archer;
+    game.world_data.mobiles[2][10] = Some(Fiend { info: archer });
+    ticks(&mut game, 15);
+    assert!(game.world_data.fiends.contains(&(10, 2)));
+    assert!(game.world_data.fiends.contains(&(14, 2)));
+}
+
+#[test]
+fn fiends_ignore_a_player_who_isnt_there() {
+    let map = "
+#######-#######
+#@           Y#
+###############
+";
+    let mut game = fight(map, FiendForm::Walking, &[]);
+    let mut hunter = fiend(FiendForm::Walking);
+    hunter.player_target_distance = 1000;
+    hunter.goal_target_distance = 0;
+    common::add_fiend(&mut game, (7, 1), hunter);
+    game.world_data.remove_player();
+    assert_eq!(game.world_data.mobiles[1][1], None);
+    ticks(&mut game, 20);
+    assert_eq!(game.world_data.player_info.health, 100);
+    assert!(thing_health(&game) < 10);
+}
@@ -0,0 +1,143 @@
+//! Recording games and playing them back.
+
+extern crate ludum_dare_37;
+
+use ludum_dare_37::{Game, Settings};
+use ludum_dare_37::map::Map;
+use ludum_dare_37::model::*;
+use ludum_dare_37::replay::{Recorder, Replay, ReplayError};
+
+const ROOM: &str = "
+thing 50
+
+#####-#####
+#         #
+#         #
+#    Y    #
+#         #
+#  O      #
+#       @ #
+#         #
+#####-#####
+";
+
+fn settings() -> Settings {
+    let mut settings = Settings::new(37);
+    settings.set_map(Map::read(ROOM.as_bytes()).expect("Bad test map"));
+    settings
+}
+
+// One of every kind of command, with the tick it's given on: a walk
+// through the menus, some building and moving, then a fight.
+fn script() -> Vec<(u64, Command)> {
+    vec![(0, Command::Select),
+         (1, Command::Select),
+         (1, Command::Move(Dir::S)),
+         (2, Command::Select),
+         (2, Command::Move(Dir::E)),
+         (3, Command::Aim),
+         (3, Command::Select),
+         (4, Command::Back),
+         (4, Command::Build(Buildable::Obstacle, (5, 2))),
+         (5, Command::Build(Buildable::Turret(TurretForm::Frost), (7, 3))),
+         (5, Command::Place((3, 5), (3, 4))),
+         (6, Command::Continue),
+         (8, Command::Move(Dir::N)),
+         (9, Command::Move(Dir::W)),
+         (12, Command::Move(Dir::W)),
+         (15, Command::Move(Dir::N)),
+         (80, Command::Quit)]
+}
+
+// Play a game through, giving each command on its tick.
+fn play(settings: Settings, commands: &[(u64, Command)]) -> Game {
+    let mut game = Game::new(settings);
+    let mut commands = commands.iter().peekable();
+    while !game.is_over() {
+        while let Some(&&(tick, command)) = commands.peek() {
+            if tick > game.ticks {
+                break;
+            }
+            game.command(command);
+            commands.next();
+        }
+        game.tick();
+    }
+    game
+}
+
+fn saved(game: &Game) -> String {
+    let mut out = Vec::new();
+    game.save(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn record(settings: &Settings, commands: &[(u64, Command)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    {
+        let mut recorder = Recorder::new(&mut out, settings).unwrap();
+        for &(tick, command) in commands {
+            recorder.record(tick, command).unwrap();
+        }
+    }
+    out
+}
+
+#[test]
+fn replays_play_back_the_same_game() {
+    let played = play(settings(), &script());
+    let replay = Replay::read(record(&settings(), &script()).as_slice()).unwrap();
+    assert_eq!(replay.settings, settings());
+    assert_eq!(replay.commands, script());
+
+    let replayed = play(replay.settings, &replay.commands);
+    assert_eq!(replayed.ticks, played.ticks);
+    assert_eq!(saved(&replayed), saved(&played));
+}
+
+#[test]
+fn commands_read_back_as_written() {
+    for &(_, command) in &script() {
+        assert_eq!(command.to_string().parse(), Ok(command));
+    }
+    for &buildable in BUILDABLES.iter() {
+        let command = Command::Build(buildable, (1, 2));
+        assert_eq!(command.to_string().parse(), Ok(command));
+    }
+    assert_eq!("move up".parse::<Command>(), Err(()));
+    assert_eq!("build castle 1 2".parse::<Command>(), Err(()));
+}
+
+#[test]
+fn replays_need_the_whole_map() {
+    let recorded = String::from_utf8(record(&settings(), &script())).unwrap();
+    // Cut the file off half way through the map.
+    let cut = recorded.lines().take(9).collect::<Vec<_>>().join("\n");
+    match Replay::read(cut.as_bytes()) {
+        Err(ReplayError::Parse { msg, .. }) => assert_eq!(msg, "the map is cut short"),
+        Err(err) => panic!("Wrong error: {}", err),
+        Ok(_) => panic!("A cut-short map was read"),
+    }
+}
+
+#[test]
+fn replays_need_a_header() {
+    assert!(Replay::read("seed 37\n".as_bytes()).is_err());
+}
+
+#[test]
+fn small_rooms_need_a_map() {
+    let mut settings = Settings::new(37);
+    settings.set_map(Map::read("#-###\n#Y @#\n#   #\n#####".as_bytes()).unwrap());
+    let replay = Replay::read(record(&settings, &script()).as_slice()).unwrap();
+    assert_eq!(replay.settings, settings);
+
+    let plain = "ludum_dare_37 replay 1\nseed 37\nsize 5x4\n";
+    match Replay::read(plain.as_bytes()) {
+        Err(ReplayError::Parse { line, msg }) => {
+            assert_eq!((line, msg.as_str()), (3, "the room is too small without a map: 5x4"))
+        }
+        Err(err) => panic!("Wrong error: {}", err),
+        Ok(_) => panic!("A plain room too small for its gates was read"),
+    }
+}
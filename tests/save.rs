@@ -0,0 +1,126 @@
+//! Saving games part way through, and carrying on with them.
+
+extern crate ludum_dare_37;
+
+use ludum_dare_37::{Game, Settings};
+use ludum_dare_37::map::Map;
+use ludum_dare_37::model::*;
+
+const ROOM: &str = "
+thing 50
+
+#####-#####
+#         #
+#         #
+#    Y    #
+#         #
+#  O      #
+#       @ #
+#         #
+#####-#####
+";
+
+const TURRET: (usize, usize) = (3, 5);
+const FROST: (usize, usize) = (7, 3);
+
+// A game in the middle of its first fight, with turrets which have
+// been upgraded and aimed, and a trained-up player.
+fn mid_fight() -> Game {
+    let mut settings = Settings::new(37);
+    settings.set_map(Map::read(ROOM.as_bytes()).expect("Bad test map"));
+    settings.starting_cash = 1000;
+    let mut game = Game::new(settings);
+    game.command(Command::Select);
+    game.command(Command::Build(Buildable::Turret(TurretForm::Frost), FROST));
+    game.command(Command::Build(Buildable::Obstacle, (5, 2)));
+    {
+        let world_data = &mut game.world_data;
+        world_data.upgrade_turret(TURRET, TurretStat::Damage);
+        world_data.upgrade_turret(TURRET, TurretStat::Range);
+        world_data.upgrade_turret(FROST, TurretStat::Cooldown);
+        world_data.aim_turret(TURRET);
+        world_data.aim_turret(TURRET);
+        world_data.upgrade_player(PlayerStat::Health);
+        world_data.upgrade_player(PlayerStat::Damage);
+    }
+    game.command(Command::Continue);
+    for _ in 0..12 {
+        game.tick();
+    }
+    game.command(Command::Move(Dir::N));
+    game
+}
+
+fn saved(game: &Game) -> String {
+    let mut out = Vec::new();
+    game.save(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn reload(game: &Game) -> Game {
+    Game::load(saved(game).as_bytes()).unwrap_or_else(|err| panic!("Couldn't load: {}", err))
+}
+
+fn turret(game: &Game, (x, y): (usize, usize)) -> TurretInfo {
+    match game.world_data.statics[y][x] {
+        Some(Turret { info }) => info,
+        sta => panic!("Expected a turret at ({}, {}) (got {:?})", x, y, sta),
+    }
+}
+
+#[test]
+fn saves_load_as_they_were() {
+    let game = mid_fight();
+    assert!(!game.world_data.fiends.is_empty(), "There should be fiends about");
+    assert!(game.state != Startup);
+
+    let loaded = reload(&game);
+    assert_eq!(saved(&loaded), saved(&game));
+    assert_eq!(loaded.ticks, game.ticks);
+    assert_eq!(loaded.settings, Settings { map: None, ..game.settings.clone() });
+    assert!(loaded.state == game.state);
+    assert_eq!(loaded.world_data.rng.state(), game.world_data.rng.state());
+    assert_eq!(loaded.world_data.player_info, game.world_data.player_info);
+    assert_eq!(loaded.world_data.fiends, game.world_data.fiends);
+    assert_eq!(loaded.world_data.turrets, game.world_data.turrets);
+    assert_eq!(loaded.world_data.obstacles, game.world_data.obstacles);
+    for &xy in &[TURRET, FROST] {
+        assert_eq!(turret(&loaded, xy), turret(&game, xy));
+    }
+    for &(x, y) in &game.world_data.fiends {
+        assert_eq!(loaded.world_data.mobiles[y][x], game.world_data.mobiles[y][x]);
+    }
+}
+
+#[test]
+fn upgrades_and_aim_are_kept() {
+    let loaded = reload(&mid_fight());
+    let info = turret(&loaded, TURRET);
+    assert_eq!(info.targeting, Targeting::Strongest);
+    assert_eq!(info.tiers[TurretStat::Damage.index()], 1);
+    assert_eq!(info.tiers[TurretStat::Range.index()], 1);
+    assert_eq!(turret(&loaded, FROST).form, TurretForm::Frost);
+    assert_eq!(loaded.world_data.player_info.tiers, [1, 1, 0, 0]);
+}
+
+#[test]
+fn loaded_games_carry_on_the_same() {
+    let mut game = mid_fight();
+    let mut loaded = reload(&game);
+    for _ in 0..50 {
+        game.tick();
+        loaded.tick();
+    }
+    assert_eq!(saved(&loaded), saved(&game));
+}
+
+#[test]
+fn small_rooms_from_maps_load() {
+    let mut settings = Settings::new(37);
+    settings.set_map(Map::read("#-###\n#Y @#\n#   #\n#####".as_bytes()).unwrap());
+    let mut game = Game::new(settings);
+    game.command(Command::Select);
+    let loaded = reload(&game);
+    assert_eq!((loaded.settings.width, loaded.settings.height), (5, 4));
+    assert_eq!(saved(&loaded), saved(&game));
+}
@@ -0,0 +1,114 @@
+//! Scoring games, and the high score table.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::Game;
+use ludum_dare_37::model::*;
+use ludum_dare_37::scores::{HighScores, Score, MAX_ENTRIES};
+
+const ROOM: &str = "
+thing 100
+
+#####-#####
+#         #
+#    Y    #
+#         #
+#@        #
+#####-#####
+";
+
+fn game() -> Game {
+    Game::new(common::settings(ROOM))
+}
+
+fn table(points: &[usize]) -> HighScores {
+    let mut scores = HighScores::default();
+    for (i, &points) in points.iter().enumerate() {
+        scores.add(format!("Player {}", i + 1), Score::new(0, points / 10, 0));
+    }
+    scores
+}
+
+fn names(scores: &HighScores) -> Vec<&str> {
+    scores.entries.iter().map(|entry| entry.name.as_str()).collect()
+}
+
+#[test]
+fn lost_waves_dont_count() {
+    let mut game = game();
+    game.world_data.wave = 5;
+    game.world_data.kills = 12;
+    game.world_data.statics[2][5] = Some(Goal {
+        health: 25,
+        max_health: 100,
+    });
+    assert_eq!(Score::of(&game), Score::new(5, 12, 25));
+
+    game.state = GameOver { msg: "You have died!".to_string() };
+    assert_eq!(Score::of(&game), Score::new(4, 12, 25));
+}
+
+#[test]
+fn better_games_score_more() {
+    let base = Score::new(3, 10, 50).points;
+    assert!(Score::new(4, 10, 50).points > base);
+    assert!(Score::new(3, 11, 50).points > base);
+    assert!(Score::new(3, 10, 51).points > base);
+}
+
+#[test]
+fn player_kills_count() {
+    let mut game = game();
+    let info = FiendInfo { value: 5, slowed: 2, ..common::rat(1) };
+    common::add_fiend(&mut game, (2, 4), info);
+    common::start_fight(&mut game);
+    game.command(Command::Move(Dir::E));
+    game.tick();
+    assert_eq!(game.world_data.kills, 1);
+}
+
+#[test]
+fn the_best_scores_come_first() {
+    let mut scores = table(&[100, 300, 200]);
+    assert_eq!(names(&scores), ["Player 2", "Player 3", "Player 1"]);
+
+    // Ties go below what's already there.
+    assert_eq!(scores.add("Latecomer".to_string(), Score::new(0, 20, 0)), Some(2));
+    assert_eq!(names(&scores), ["Player 2", "Player 3", "Latecomer", "Player 1"]);
+}
+
+#[test]
+fn only_the_best_scores_are_kept() {
+    let mut scores = table(&[100; MAX_ENTRIES]);
+    assert!(!scores.qualifies(&Score::new(0, 10, 0)));
+    assert_eq!(scores.add("Also ran".to_string(), Score::new(0, 10, 0)), None);
+    assert_eq!(scores.add("Winner".to_string(), Score::new(0, 11, 0)), Some(0));
+    assert_eq!(scores.entries.len(), MAX_ENTRIES);
+    assert_eq!(scores.entries[0].name, "Winner");
+}
+
+#[test]
+fn names_are_tidied() {
+    let mut scores = HighScores::default();
+    scores.add("  ".to_string(), Score::new(1, 0, 0));
+    scores.add("Two\nlines".to_string(), Score::new(0, 0, 0));
+    assert_eq!(names(&scores), ["Anonymous", "Twolines"]);
+}
+
+#[test]
+fn tables_survive_being_written_and_read() {
+    let mut scores = table(&[100, 300, 200]);
+    scores.add("Spaced Out Name".to_string(), Score::new(7, 31, 64));
+    let mut out = Vec::new();
+    scores.write(&mut out).unwrap();
+    let read = HighScores::read(out.as_slice()).unwrap();
+    assert_eq!(read, scores);
+}
+
+#[test]
+fn bad_tables_are_rejected() {
+    assert!(HighScores::read("nonsense\n".as_bytes()).is_err());
+    assert!(HighScores::read("ludum_dare_37 scores 1\n12 three 4 5 Bob\n".as_bytes()).is_err());
+}
@@ -0,0 +1,255 @@
+//! Which fiends turrets shoot at, and what different kinds of turret
+//! do to them.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::Game;
+use ludum_dare_37::model::*;
+
+const ROOM: &str = "
+#-###########
+#           #
+#           #
+#     O     #
+#           #
+#          Y#
+#@          #
+#############
+";
+
+// The turret is at (6, 3), and the Thing at (11, 5).
+const TURRET: (usize, usize) = (6, 3);
+
+// The same room, with an obstacle to the right of the turret and a wall
+// below it.
+const BLOCKED_ROOM: &str = "
+#-###########
+#           #
+#           #
+#     O =   #
+#           #
+#     #    Y#
+#@          #
+#############
+";
+
+type FiendSpec = ((usize, usize), &'static str, usize, usize);
+
+// A fight with fiends at the given places, with the given prefix,
+// health and value, and the turret aiming as given. The fiends are
+// slowed, so they stay put for the first tick.
+fn fight(targeting: Targeting, fiends: &[FiendSpec]) -> Game {
+    fight_in(ROOM, targeting, fiends)
+}
+
+fn fight_in(map: &str, targeting: Targeting, fiends: &[FiendSpec]) -> Game {
+    let mut game = Game::new(common::settings(map));
+    for &(xy, prefix, health, value) in fiends {
+        let mut info = common::rat(health);
+        info.name.prefix = prefix;
+        info.value = value;
+        info.slowed = 2;
+        common::add_fiend(&mut game, xy, info);
+    }
+
+    let world_data = &mut game.world_data;
+    match world_data.statics[TURRET.1][TURRET.0] {
+        Some(Turret { mut info }) => {
+            info.targeting = targeting;
+            world_data.statics[TURRET.1][TURRET.0] = Some(Turret { info });
+        }
+        sta => panic!("The turret is missing (got {:?})", sta),
+    }
+
+    common::start_fight(&mut game);
+    game
+}
+
+// Where the turret's arrow is headed, after the first tick.
+fn aimed_at(game: &mut Game) -> (bool, bool) {
+    game.tick();
+    let arrows = game.world_data.arrows.iter().collect::<Vec<_>>();
+    assert_eq!(arrows.len(), 1, "Expected one arrow");
+    let (x, y) = *arrows[0];
+    match game.world_data.mobiles[y][x] {
+        Some(Arrow { info }) => info.dir,
+        mob => panic!("Expected an arrow (got {:?})", mob),
+    }
+}
+
+// Fiends to the left (nearer the turret) and the right (nearer the
+// Thing), one above and one below the turret, so the arrow's direction
+// gives away which it was fired at.
+const LEFT: (usize, usize) = (3, 1);
+const RIGHT: (usize, usize) = (10, 5);
+
+#[test]
+fn turrets_shoot_the_nearest_fiend() {
+    let mut game = fight(Targeting::Nearest, &[(LEFT, "", 500, 5), (RIGHT, "", 500, 5)]);
+    assert_eq!(aimed_at(&mut game), (false, false));
+}
+
+#[test]
+fn turrets_shoot_the_fiend_nearest_the_thing() {
+    let mut game = fight(Targeting::Thing, &[(LEFT, "", 500, 5), (RIGHT, "", 500, 5)]);
+    assert_eq!(aimed_at(&mut game), (true, true));
+}
+
+#[test]
+fn turrets_shoot_the_strongest_fiend() {
+    let mut game = fight(Targeting::Strongest, &[(LEFT, "", 500, 5), (RIGHT, "", 900, 5)]);
+    assert_eq!(aimed_at(&mut game), (true, true));
+}
+
+#[test]
+fn turrets_shoot_the_weakest_fiend() {
+    let mut game = fight(Targeting::Weakest, &[(LEFT, "", 900, 5), (RIGHT, "", 500, 5)]);
+    assert_eq!(aimed_at(&mut game), (true, true));
+}
+
+#[test]
+fn turrets_shoot_the_richest_fiend() {
+    let mut game = fight(Targeting::Richest, &[(LEFT, "", 500, 5), (RIGHT, "", 500, 50)]);
+    assert_eq!(aimed_at(&mut game), (true, true));
+}
+
+#[test]
+fn turrets_shoot_bosses_first() {
+    let mut game = fight(Targeting::Boss, &[(LEFT, "", 500, 5), (RIGHT, "grim ", 500, 5)]);
+    assert_eq!(aimed_at(&mut game), (true, true));
+}
+
+#[test]
+fn turrets_shoot_the_nearest_fiend_without_bosses() {
+    let mut game = fight(Targeting::Boss, &[(LEFT, "", 500, 5), (RIGHT, "", 500, 5)]);
+    assert_eq!(aimed_at(&mut game), (false, false));
+}
+
+// Whether the turret has fired, going by whether it's cooling down.
+fn fired(game: &mut Game) -> bool {
+    game.tick();
+    match game.world_data.statics[TURRET.1][TURRET.0] {
+        Some(Turret { info }) => info.cooldown > 0,
+        sta => panic!("The turret is missing (got {:?})", sta),
+    }
+}
+
+#[test]
+fn turrets_dont_shoot_through_obstacles() {
+    let mut game = fight_in(BLOCKED_ROOM, Targeting::Nearest, &[((10, 3), "", 500, 5)]);
+    assert!(!fired(&mut game));
+}
+
+#[test]
+fn turrets_dont_shoot_through_walls() {
+    let mut game = fight_in(BLOCKED_ROOM, Targeting::Nearest, &[((6, 6), "", 500, 5)]);
+    assert!(!fired(&mut game));
+}
+
+#[test]
+fn turrets_shoot_past_obstacles_they_can_see_around() {
+    let mut game = fight_in(BLOCKED_ROOM, Targeting::Nearest, &[((10, 1), "", 500, 5)]);
+    assert!(fired(&mut game));
+}
+
+#[test]
+fn turrets_shoot_what_they_can_see() {
+    let fiends = [(LEFT, "", 900, 5), ((10, 3), "", 100, 5)];
+    let mut game = fight_in(BLOCKED_ROOM, Targeting::Weakest, &fiends);
+    assert_eq!(aimed_at(&mut game), (false, false));
+}
+
+#[test]
+fn turrets_dont_shoot_through_fiends() {
+    let fiends = [(LEFT, "", 500, 5), ((9, 6), "", 900, 5), ((10, 7), "", 100, 5)];
+    let mut game = fight(Targeting::Weakest, &fiends);
+    assert_eq!(aimed_at(&mut game), (false, false));
+}
+
+#[test]
+fn turrets_are_credited_with_kills() {
+    let mut game = fight(Targeting::Nearest, &[((8, 3), "", 1, 5)]);
+    for _ in 0..10 {
+        game.tick();
+    }
+    assert!(game.world_data.fiends.is_empty(), "The fiend wasn't killed");
+    match game.world_data.statics[TURRET.1][TURRET.0] {
+        Some(Turret { info }) => assert_eq!(info.kills, 1),
+        sta => panic!("The turret is missing (got {:?})", sta),
+    }
+}
+
+// Swap the turret for a new one of another kind.
+fn rebuild(game: &mut Game, form: TurretForm) {
+    game.world_data.statics[TURRET.1][TURRET.0] = Some(Turret { info: form.info() });
+}
+
+// The health of every fiend, from top left to bottom right, once the
+// first one is hit.
+fn first_hit(game: &mut Game) -> Vec<usize> {
+    let health = |game: &Game| {
+        game.world_data
+            .fiends
+            .iter()
+            .map(|&(x, y)| match game.world_data.mobiles[y][x] {
+                Some(Fiend { info }) => info.health,
+                mob => panic!("Expected a fiend (got {:?})", mob),
+            })
+            .collect::<Vec<_>>()
+    };
+    let before = health(game);
+    for _ in 0..10 {
+        game.tick();
+        let after = health(game);
+        if after != before {
+            return after;
+        }
+    }
+    panic!("Nothing was hit");
+}
+
+// The same turret, with a tough Thing close by for fiends to stand
+// still and attack.
+const THING_ROOM: &str = "
+thing 1000
+
+#-###########
+#           #
+#           #
+#     O   Y #
+#           #
+#           #
+#@          #
+#############
+";
+
+// Fiends either side of the corner of the Thing, next to each other.
+const NEXT_TO_THING: [FiendSpec; 2] = [((9, 3), "", 500, 5), ((10, 2), "", 500, 5)];
+
+#[test]
+fn mortars_hurt_everything_around_where_they_land() {
+    let mut game = fight_in(THING_ROOM, Targeting::Nearest, &NEXT_TO_THING);
+    rebuild(&mut game, TurretForm::Mortar);
+    assert_eq!(first_hit(&mut game), [300, 300]);
+}
+
+#[test]
+fn other_turrets_only_hurt_what_they_hit() {
+    let mut game = fight_in(THING_ROOM, Targeting::Nearest, &NEXT_TO_THING);
+    rebuild(&mut game, TurretForm::Basic);
+    assert_eq!(first_hit(&mut game), [200, 500]);
+}
+
+#[test]
+fn frost_slows_what_it_hits() {
+    let mut game = fight_in(THING_ROOM, Targeting::Nearest, &NEXT_TO_THING[..1]);
+    rebuild(&mut game, TurretForm::Frost);
+    first_hit(&mut game);
+    let &(x, y) = game.world_data.fiends.iter().next().unwrap();
+    match game.world_data.mobiles[y][x] {
+        Some(Fiend { info }) => assert_eq!(info.slowed, TurretForm::Frost.slow()),
+        mob => panic!("Expected a fiend (got {:?})", mob),
+    }
+}
@@ -0,0 +1,121 @@
+//! Spending cash on turrets and the player between waves.
+
+extern crate ludum_dare_37;
+
+mod common;
+
+use ludum_dare_37::Game;
+use ludum_dare_37::model::*;
+
+// A room with a single turret at (1, 1).
+const ROOM: &str = "
+#####-#####
+#O        #
+#    Y    #
+#       @ #
+#####-#####
+";
+
+const TURRET: (usize, usize) = (1, 1);
+
+// A game in the construct phase, with the given cash to spend.
+fn game(cash: usize) -> Game {
+    let mut settings = common::settings(ROOM);
+    settings.starting_cash = cash;
+    let mut game = Game::new(settings);
+    game.command(Command::Select);
+    game
+}
+
+fn turret(game: &Game) -> TurretInfo {
+    match game.world_data.statics[TURRET.1][TURRET.0] {
+        Some(Turret { info }) => info,
+        sta => panic!("The turret is missing (got {:?})", sta),
+    }
+}
+
+#[test]
+fn turrets_are_upgraded_through_the_menus() {
+    let mut game = game(100);
+    let before = turret(&game);
+    let cost = TurretStat::Range.cost(&before).unwrap();
+
+    // Pick Upgrade, then the turret, then Range.
+    for &command in &[Command::Move(Dir::S),
+                      Command::Move(Dir::S),
+                      Command::Select,
+                      Command::Select,
+                      Command::Move(Dir::S),
+                      Command::Select] {
+        game.command(command);
+    }
+
+    let after = turret(&game);
+    assert_eq!(after.range, before.range + 10);
+    assert_eq!(after.tiers[TurretStat::Range.index()], 1);
+    assert_eq!(game.world_data.cash, 100 - cost);
+    // The next tier costs more.
+    assert!(TurretStat::Range.cost(&after).unwrap() > cost);
+}
+
+#[test]
+fn stats_stop_at_the_top_tier() {
+    let mut game = game(10_000);
+    for _ in 0..MAX_TIER {
+        game.world_data.upgrade_turret(TURRET, TurretStat::Damage);
+    }
+    let cash = game.world_data.cash;
+    let info = turret(&game);
+    assert_eq!(TurretStat::Damage.cost(&info), None);
+
+    game.world_data.upgrade_turret(TURRET, TurretStat::Damage);
+    assert_eq!(turret(&game), info);
+    assert_eq!(game.world_data.cash, cash);
+    assert_eq!(game.world_data.log[0], "Damage is already fully upgraded.");
+}
+
+#[test]
+fn the_player_is_trained_through_the_menus() {
+    let mut game = game(100);
+    let before = game.world_data.player_info;
+
+    // Pick Train, then Armour.
+    for &command in &[Command::Move(Dir::N),
+                      Command::Move(Dir::N),
+                      Command::Move(Dir::N),
+                      Command::Select,
+                      Command::Move(Dir::N),
+                      Command::Move(Dir::N),
+                      Command::Select] {
+        game.command(command);
+    }
+
+    let after = game.world_data.player_info;
+    assert_eq!(after.armour_factor, before.armour_factor + 25);
+    assert_eq!(after.tiers[PlayerStat::Armour.index()], 1);
+    assert_eq!(game.world_data.cash, 100 - PlayerStat::Armour.cost(&before).unwrap());
+}
+
+// How much health the player loses to one hit from a fiend which does
+// 100 damage.
+fn hit_with_armour(armour: usize) -> usize {
+    let mut game = game(0);
+    game.world_data.player_info.armour_factor = armour;
+    let hunter = FiendInfo {
+        damage_factor: 100,
+        player_target_distance: 1000,
+        goal_target_distance: 0,
+        ..common::rat(100)
+    };
+    common::add_fiend(&mut game, (7, 3), hunter);
+    common::start_fight(&mut game);
+    game.tick();
+    100 - game.world_data.player_info.health
+}
+
+#[test]
+fn armour_blunts_fiends_hits() {
+    assert_eq!(hit_with_armour(0), 100);
+    assert_eq!(hit_with_armour(100), 50);
+    assert_eq!(hit_with_armour(300), 25);
+}
@@ -0,0 +1,71 @@
+//! The 'waves' command's summaries of what's in each wave.
+
+// The command is part of the game itself, which needs the terminal
+// front-end.
+#![cfg(feature = "curses")]
+
+extern crate ludum_dare_37;
+
+use ludum_dare_37::fiends::make_wave;
+use ludum_dare_37::model::*;
+use ludum_dare_37::rng::GameRng;
+
+use std::process::Command;
+
+// What the command prints with the given arguments.
+fn waves(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ludum_dare_37"))
+        .arg("waves")
+        .args(args)
+        .output()
+        .expect("Couldn't run the game");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// The fiends in waves up to 'last' of a game with the given seed.
+fn fiends(seed: u64, difficulty: Difficulty, last: usize) -> Vec<Vec<FiendInfo>> {
+    let mut rng = GameRng::new(seed);
+    (1..last + 1).map(|wave| make_wave(wave, difficulty, &mut rng)).collect()
+}
+
+#[test]
+fn csv_adds_up_each_wave() {
+    let csv = waves(&["--seed", "5", "--waves", "3-4", "--format", "csv"]);
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(),
+               Some("wave,boss,fiends,hp,effective_hp,damage,armour,value,hunter,raider,wrecker,\
+                     archer"));
+
+    let all = fiends(5, Difficulty::Normal, 4);
+    for (line, wave) in lines.zip(3..) {
+        let fields = line.split(',').collect::<Vec<_>>();
+        let wave_fiends = &all[wave - 1];
+        let total = |f: fn(&FiendInfo) -> usize| wave_fiends.iter().map(f).sum::<usize>();
+        assert_eq!(fields[0], wave.to_string());
+        assert_eq!(fields[2], wave_fiends.len().to_string());
+        assert_eq!(fields[3], total(|f| f.max_health).to_string());
+        assert_eq!(fields[4], total(|f| f.effective_health()).to_string());
+        assert_eq!(fields[5], total(|f| f.damage_factor).to_string());
+        assert_eq!(fields[6], total(|f| f.armour_factor).to_string());
+        assert_eq!(fields[7], total(|f| f.value).to_string());
+        for (i, archetype) in FIEND_ARCHETYPES.iter().enumerate() {
+            let count = wave_fiends.iter().filter(|f| f.archetype == *archetype).count();
+            assert_eq!(fields[8 + i], count.to_string());
+        }
+    }
+    assert_eq!(csv.lines().count(), 3);
+}
+
+#[test]
+fn json_has_the_waves_asked_for() {
+    let json = waves(&["--seed", "5", "--waves", "3-4", "--format", "json", "--difficulty",
+                       "hard"]);
+    let all = fiends(5, Difficulty::Hard, 4);
+    assert!(!json.contains("\"wave\": 2,"));
+    for wave in 3..5 {
+        let summary = format!("{{\"wave\": {}, \"boss\": ", wave);
+        let line = json.lines().find(|line| line.contains(&summary)).expect("Missing wave");
+        assert!(line.contains(&format!("\"fiends\": {},", all[wave - 1].len())), "{}", line);
+    }
+}